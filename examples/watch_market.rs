@@ -6,6 +6,7 @@ use futures::stream::StreamExt;
 use std::io;
 use std::io::Write;
 
+#[allow(clippy::result_large_err)]
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
@@ -0,0 +1,38 @@
+use dotenvy::dotenv;
+use ftx::options::Options;
+use ftx::ws::Result;
+use ftx::ws::{OrderbookEvent, OrderbookStream, ReconnectPolicy, Ws};
+use futures::stream::StreamExt;
+
+#[allow(clippy::result_large_err)]
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+
+    let websocket = Ws::connect(Options::from_env())
+        .await?
+        .with_reconnect(ReconnectPolicy::default());
+    let mut orderbooks = OrderbookStream::new(websocket);
+
+    let market = String::from("BTC-PERP");
+    orderbooks.subscribe(&[market.to_owned()]).await?;
+
+    loop {
+        match orderbooks.next().await.expect("No data received")? {
+            OrderbookEvent::Snapshot(orderbook) => {
+                println!(
+                    "Snapshot of {}: {:?}",
+                    orderbook.symbol,
+                    orderbook.best_bid_and_ask()
+                );
+            }
+            OrderbookEvent::Update(symbol, _) => {
+                let orderbook = orderbooks.orderbook(&symbol).unwrap();
+                println!("{} mid price: {:?}", symbol, orderbook.mid_price());
+            }
+            OrderbookEvent::Resyncing(symbol) => {
+                println!("Resyncing {}", symbol);
+            }
+        }
+    }
+}
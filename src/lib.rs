@@ -1,4 +1,8 @@
+#[cfg(all(feature = "ws", any(test, feature = "mock")))]
+pub mod mock;
 pub mod options;
 pub mod rest;
 #[cfg(feature = "ws")]
+pub mod trading;
+#[cfg(feature = "ws")]
 pub mod ws;
@@ -0,0 +1,132 @@
+//! Constructors for fixtures with sensible defaults for everything that is
+//! usually irrelevant to a test.
+
+use crate::rest::{Id, Market, MarketType, Position, Side, Trade};
+use crate::ws::{Fill, Liquidity, Ticker};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+
+/// A market, which is a spot market if the name contains a slash (e.g.
+/// `BTC/USD`) and a future otherwise (e.g. `BTC-PERP`).
+pub fn market(name: &str, price_increment: Decimal, size_increment: Decimal) -> Market {
+    let (market_type, underlying, base_currency, quote_currency) = match name.split_once('/') {
+        Some((base, quote)) => (
+            MarketType::Spot,
+            None,
+            Some(base.to_owned()),
+            Some(quote.to_owned()),
+        ),
+        None => (
+            MarketType::Future,
+            name.split('-').next().map(ToOwned::to_owned),
+            None,
+            None,
+        ),
+    };
+
+    Market {
+        market_type,
+        name: name.to_owned(),
+        underlying,
+        base_currency,
+        quote_currency,
+        enabled: true,
+        ask: None,
+        bid: None,
+        last: None,
+        post_only: false,
+        price_increment,
+        size_increment,
+        restricted: false,
+        min_provide_size: size_increment,
+        price: None,
+        high_leverage_fee_exempt: false,
+        change1h: Decimal::ZERO,
+        change24h: Decimal::ZERO,
+        change_bod: Decimal::ZERO,
+        quote_volume24h: Decimal::ZERO,
+        volume_usd24h: Decimal::ZERO,
+    }
+}
+
+pub fn trade(id: Id, side: Side, price: Decimal, size: Decimal, time: DateTime<Utc>) -> Trade {
+    Trade {
+        id,
+        liquidation: false,
+        price,
+        side,
+        size,
+        time,
+    }
+}
+
+/// A taker fill without fees.
+pub fn fill(
+    id: Id,
+    market: &str,
+    side: Side,
+    price: Decimal,
+    size: Decimal,
+    time: DateTime<Utc>,
+) -> Fill {
+    let (base_currency, quote_currency) = match market.split_once('/') {
+        Some((base, quote)) => (Some(base.to_owned()), Some(quote.to_owned())),
+        None => (None, None),
+    };
+
+    Fill {
+        id,
+        market: Some(market.to_owned()),
+        future: base_currency.is_none().then(|| market.to_owned()),
+        base_currency,
+        quote_currency,
+        r#type: "order".to_owned(),
+        side,
+        price,
+        size,
+        order_id: None,
+        trade_id: None,
+        time,
+        fee: Decimal::ZERO,
+        fee_rate: Decimal::ZERO,
+        fee_currency: "USD".to_owned(),
+        liquidity: Liquidity::Taker,
+    }
+}
+
+/// A ticker with unit sizes, timestamped now.
+pub fn ticker(bid: Decimal, ask: Decimal, last: Decimal) -> Ticker {
+    Ticker {
+        bid,
+        ask,
+        bid_size: Decimal::ONE,
+        ask_size: Decimal::ONE,
+        last,
+        time: Utc::now(),
+    }
+}
+
+/// A position without margin requirements or open orders.
+pub fn position(future: &str, net_size: Decimal, entry_price: Decimal) -> Position {
+    Position {
+        cost: net_size * entry_price,
+        entry_price: Some(entry_price),
+        estimated_liquidation_price: None,
+        future: future.to_owned(),
+        initial_margin_requirement: Decimal::ZERO,
+        long_order_size: Decimal::ZERO,
+        maintenance_margin_requirement: Decimal::ZERO,
+        net_size,
+        open_size: net_size.abs(),
+        realized_pnl: Decimal::ZERO,
+        short_order_size: Decimal::ZERO,
+        side: if net_size.is_sign_negative() {
+            Side::Sell
+        } else {
+            Side::Buy
+        },
+        size: net_size.abs(),
+        unrealized_pnl: Decimal::ZERO,
+        collateral_used: Decimal::ZERO,
+    }
+}
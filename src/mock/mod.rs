@@ -0,0 +1,464 @@
+//! This module provides an in-process mock of the FTX exchange.
+//!
+//! `MockExchange` serves the REST API over HTTP and the websocket API on a
+//! second port of the local machine, speaking the same JSON envelopes as FTX.
+//! Point `Rest` and `Ws` at it with `MockExchange::options` and script its
+//! behaviour through fixtures for markets, orderbooks, trades, fills and
+//! order lifecycles, so that tests run deterministically without network
+//! access.
+
+mod fixtures;
+mod rest;
+mod ws;
+
+pub use fixtures::*;
+
+use crate::options::{Endpoint, Options};
+use crate::rest::{Candle, Future, Id, Market, OrderInfo, Position, Resolution, Symbol, Trade};
+use crate::ws::{Channel, Fill, GroupedOrderbook, Orderbook, ResponseData, Ticker, Type};
+use http::{HeaderMap, Method, StatusCode};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+/// The header prefix used by the mock exchange, identical to FTX's.
+pub const HEADER_PREFIX: &str = "FTX";
+
+/// A scripted response of the REST API.
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    pub status: StatusCode,
+    pub body: Value,
+}
+
+impl MockResponse {
+    /// A `SuccessResponse` wrapping `result`.
+    pub fn success<T: Serialize>(result: T) -> Self {
+        Self {
+            status: StatusCode::OK,
+            body: json!({
+                "success": true,
+                "result": result,
+            }),
+        }
+    }
+
+    /// An `ErrorResponse` with the given HTTP status and error message.
+    pub fn error(status: StatusCode, error: &str) -> Self {
+        Self {
+            status,
+            body: json!({
+                "success": false,
+                "error": error,
+            }),
+        }
+    }
+}
+
+/// A request received by the REST API of the mock exchange.
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: Method,
+    /// Path without the `/api` prefix and without query, e.g. `/orders`
+    pub path: String,
+    /// Raw query string, empty if none was sent
+    pub query: String,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+/// Determines what happens to orders after they have been queued.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OrderBehavior {
+    /// The order is accepted and rests on the book until it is cancelled.
+    Rest,
+    /// The order is accepted and filled completely right away.
+    Fill,
+    /// The order is rejected during processing, e.g. like a post-only order
+    /// that would have crossed the book.
+    Reject,
+}
+
+// `#[default]` on variants requires a newer Rust than the supported 1.54
+#[allow(unknown_lints, clippy::derivable_impls)]
+impl Default for OrderBehavior {
+    fn default() -> Self {
+        OrderBehavior::Rest
+    }
+}
+
+struct Route {
+    method: Method,
+    path: String,
+    response: MockResponse,
+    once: bool,
+}
+
+struct Client {
+    sender: UnboundedSender<Message>,
+    channels: Vec<Channel>,
+    authenticated: bool,
+}
+
+#[derive(Default)]
+struct State {
+    credentials: Option<(String, String)>,
+    markets: Vec<Market>,
+    futures: Vec<Future>,
+    orderbooks: HashMap<Symbol, Orderbook>,
+    trades: HashMap<Symbol, Vec<Trade>>,
+    candles: HashMap<(Symbol, u32), Vec<Candle>>,
+    fills: Vec<Fill>,
+    positions: Vec<Position>,
+    orders: BTreeMap<Id, OrderInfo>,
+    next_id: Id,
+    order_behavior: OrderBehavior,
+    scripted_behaviors: VecDeque<OrderBehavior>,
+    routes: Vec<Route>,
+    requests: Vec<RecordedRequest>,
+    clients: HashMap<usize, Client>,
+    next_client: usize,
+    ignore_pings: bool,
+}
+
+impl State {
+    fn next_id(&mut self) -> Id {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// The groupings of all grouped orderbook channels of `market` that
+    /// clients are subscribed to.
+    fn groupings(&self, market: &str) -> Vec<Decimal> {
+        let mut groupings = Vec::new();
+        for client in self.clients.values() {
+            for channel in &client.channels {
+                if let Channel::GroupedOrderbook {
+                    market: m,
+                    grouping,
+                } = channel
+                {
+                    if m == market && !groupings.contains(grouping) {
+                        groupings.push(*grouping);
+                    }
+                }
+            }
+        }
+        groupings
+    }
+
+    /// Sends a message on `channel` to every websocket client subscribed to it.
+    fn publish(&mut self, channel: &Channel, r#type: Type, data: Option<Value>) {
+        let (name, market) = ws::channel_name(channel);
+        let text = json!({
+            "channel": name,
+            "market": market,
+            "type": r#type,
+            "data": data,
+        })
+        .to_string();
+
+        for client in self.clients.values() {
+            if client.channels.contains(channel) {
+                let _ = client.sender.send(Message::Text(text.clone()));
+            }
+        }
+    }
+}
+
+/// An in-process FTX exchange serving the REST and websocket APIs on
+/// local ports. The servers are shut down when the value is dropped.
+pub struct MockExchange {
+    state: Arc<Mutex<State>>,
+    rest_addr: SocketAddr,
+    ws_addr: SocketAddr,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MockExchange {
+    /// Starts the REST and websocket servers on random local ports.
+    pub async fn start() -> io::Result<Self> {
+        let state = Arc::new(Mutex::new(State::default()));
+        let (rest_addr, rest_task) = rest::serve(state.clone())?;
+        let (ws_addr, ws_task) = ws::serve(state.clone()).await?;
+
+        Ok(Self {
+            state,
+            rest_addr,
+            ws_addr,
+            tasks: vec![rest_task, ws_task],
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    pub fn rest_addr(&self) -> SocketAddr {
+        self.rest_addr
+    }
+
+    pub fn ws_addr(&self) -> SocketAddr {
+        self.ws_addr
+    }
+
+    /// The endpoint pointing at this mock exchange.
+    pub fn endpoint(&self) -> Endpoint {
+        Endpoint::Custom {
+            rest: format!("http://{}/api", self.rest_addr),
+            ws: format!("ws://{}/ws", self.ws_addr),
+            header_prefix: HEADER_PREFIX.to_owned(),
+        }
+    }
+
+    /// Unauthenticated options pointing at this mock exchange.
+    pub fn options(&self) -> Options {
+        Options::default().with_endpoint(self.endpoint())
+    }
+
+    /// Only accept requests signed with these API credentials. Without
+    /// credentials, any key is accepted and signatures are not verified.
+    pub fn set_credentials(&self, key: &str, secret: &str) {
+        self.state().credentials = Some((key.to_owned(), secret.to_owned()));
+    }
+
+    /// Adds a market, or replaces the market with the same name, and
+    /// publishes it on the markets channel.
+    pub fn add_market(&self, market: Market) {
+        let mut state = self.state();
+        let data = ws::markets_data(std::slice::from_ref(&market), "update");
+        state.markets.retain(|m| m.name != market.name);
+        state.markets.push(market);
+        state.publish(&Channel::Markets, Type::Update, Some(data));
+    }
+
+    /// Removes a market and publishes it as disabled on the markets channel.
+    pub fn remove_market(&self, name: &str) {
+        let mut state = self.state();
+        let (removed, markets) = state.markets.drain(..).partition(|m| m.name == name);
+        state.markets = markets;
+        let disabled: Vec<_> = removed
+            .into_iter()
+            .map(|market: Market| Market {
+                enabled: false,
+                ..market
+            })
+            .collect();
+        if !disabled.is_empty() {
+            let data = ws::markets_data(&disabled, "update");
+            state.publish(&Channel::Markets, Type::Update, Some(data));
+        }
+    }
+
+    pub fn add_future(&self, future: Future) {
+        self.state().futures.push(future);
+    }
+
+    /// Replaces the orderbook of `market`. New subscribers of the orderbook
+    /// channel receive it as partial.
+    pub fn set_orderbook(
+        &self,
+        market: &str,
+        bids: &[(Decimal, Decimal)],
+        asks: &[(Decimal, Decimal)],
+    ) {
+        let mut orderbook = Orderbook::new(market.to_owned());
+        orderbook.bids.extend(bids.iter().cloned());
+        orderbook.asks.extend(asks.iter().cloned());
+        self.state().orderbooks.insert(market.to_owned(), orderbook);
+    }
+
+    /// Applies a diff to the orderbook of `market`, where a size of zero
+    /// removes a level, and publishes it with a correct checksum.
+    pub fn update_orderbook(
+        &self,
+        market: &str,
+        bids: &[(Decimal, Decimal)],
+        asks: &[(Decimal, Decimal)],
+    ) {
+        let mut state = self.state();
+        let groupings = state.groupings(market);
+        let orderbook = state
+            .orderbooks
+            .entry(market.to_owned())
+            .or_insert_with(|| Orderbook::new(market.to_owned()));
+        let previous: Vec<_> = groupings
+            .iter()
+            .map(|grouping| GroupedOrderbook::from_orderbook(orderbook, *grouping))
+            .collect();
+        for (price, size) in bids {
+            if size.is_zero() {
+                orderbook.bids.remove(price);
+            } else {
+                orderbook.bids.insert(*price, *size);
+            }
+        }
+        for (price, size) in asks {
+            if size.is_zero() {
+                orderbook.asks.remove(price);
+            } else {
+                orderbook.asks.insert(*price, *size);
+            }
+        }
+        let data = ws::orderbook_data(orderbook, "update", bids, asks);
+        let grouped: Vec<_> = previous
+            .iter()
+            .map(|previous| {
+                let grouped = GroupedOrderbook::from_orderbook(orderbook, previous.grouping);
+                ws::grouped_orderbook_data(&grouped, previous)
+            })
+            .collect();
+
+        state.publish(
+            &Channel::Orderbook(market.to_owned()),
+            Type::Update,
+            Some(data),
+        );
+        for (grouping, data) in groupings.into_iter().zip(grouped) {
+            let channel = Channel::GroupedOrderbook {
+                market: market.to_owned(),
+                grouping,
+            };
+            state.publish(&channel, Type::Update, Some(data));
+        }
+    }
+
+    /// Adds trades returned by the REST API.
+    pub fn add_trades(&self, market: &str, trades: Vec<Trade>) {
+        self.state()
+            .trades
+            .entry(market.to_owned())
+            .or_default()
+            .extend(trades);
+    }
+
+    /// Adds trades returned by the REST API and publishes them on the
+    /// trades channel.
+    pub fn publish_trades(&self, market: &str, trades: Vec<Trade>) {
+        let mut state = self.state();
+        state.publish(
+            &Channel::Trades(market.to_owned()),
+            Type::Update,
+            Some(json!(trades)),
+        );
+        state
+            .trades
+            .entry(market.to_owned())
+            .or_default()
+            .extend(trades);
+    }
+
+    pub fn publish_ticker(&self, market: &str, ticker: Ticker) {
+        self.state().publish(
+            &Channel::Ticker(market.to_owned()),
+            Type::Update,
+            Some(json!(ticker)),
+        );
+    }
+
+    /// Adds candles returned by the REST API for the given resolution.
+    pub fn add_candles(&self, market: &str, resolution: Resolution, candles: Vec<Candle>) {
+        self.state()
+            .candles
+            .entry((market.to_owned(), resolution.get_seconds()))
+            .or_default()
+            .extend(candles);
+    }
+
+    /// Adds fills returned by the REST API.
+    pub fn add_fills(&self, fills: Vec<Fill>) {
+        self.state().fills.extend(fills);
+    }
+
+    /// Adds a fill returned by the REST API and publishes it on the fills
+    /// channel.
+    pub fn publish_fill(&self, fill: Fill) {
+        let mut state = self.state();
+        state.publish(&Channel::Fills, Type::Update, Some(json!(fill)));
+        state.fills.push(fill);
+    }
+
+    pub fn set_positions(&self, positions: Vec<Position>) {
+        self.state().positions = positions;
+    }
+
+    /// Sets what happens to all subsequently placed orders.
+    pub fn set_order_behavior(&self, behavior: OrderBehavior) {
+        self.state().order_behavior = behavior;
+    }
+
+    /// Sets what happens to the next placed order only. Multiple calls are
+    /// applied to subsequent orders in order.
+    pub fn push_order_behavior(&self, behavior: OrderBehavior) {
+        self.state().scripted_behaviors.push_back(behavior);
+    }
+
+    /// All orders known to the exchange, ordered by ID.
+    pub fn orders(&self) -> Vec<OrderInfo> {
+        self.state().orders.values().cloned().collect()
+    }
+
+    /// Always answer requests to `method` and `path` (without `/api`) with
+    /// `response`, taking precedence over the built-in behaviour.
+    pub fn respond(&self, method: Method, path: &str, response: MockResponse) {
+        self.state().routes.push(Route {
+            method,
+            path: path.to_owned(),
+            response,
+            once: false,
+        });
+    }
+
+    /// Like `respond`, but only for the next matching request. Responses
+    /// scripted for the same route are returned in order.
+    pub fn respond_once(&self, method: Method, path: &str, response: MockResponse) {
+        self.state().routes.push(Route {
+            method,
+            path: path.to_owned(),
+            response,
+            once: true,
+        });
+    }
+
+    /// Publishes arbitrary data on a websocket channel.
+    pub fn publish(&self, channel: &Channel, r#type: Type, data: &ResponseData) {
+        self.state().publish(channel, r#type, Some(json!(data)));
+    }
+
+    /// All requests received by the REST API so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state().requests.clone()
+    }
+
+    /// The number of currently connected websocket clients.
+    pub fn ws_connections(&self) -> usize {
+        self.state().clients.len()
+    }
+
+    /// Stops answering pings with pongs, like an unresponsive server, or
+    /// starts answering them again.
+    pub fn set_ignore_pings(&self, ignore: bool) {
+        self.state().ignore_pings = ignore;
+    }
+
+    /// Closes all websocket connections.
+    pub fn disconnect_all(&self) {
+        self.state().clients.clear();
+    }
+}
+
+impl Drop for MockExchange {
+    fn drop(&mut self) {
+        self.disconnect_all();
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
@@ -0,0 +1,601 @@
+use super::{MockResponse, OrderBehavior, RecordedRequest, State, HEADER_PREFIX};
+use crate::rest::{Id, OrderInfo, OrderStatus, OrderType, Side, Trade};
+use crate::ws::{Channel, Fill, Liquidity, Type};
+use chrono::{DateTime, TimeZone, Utc};
+use hmac_sha256::HMAC;
+use http::{Method, StatusCode};
+use hyper::{
+    body::to_bytes,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server,
+};
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+// `io::Error::other` requires a newer Rust than the supported 1.54
+#[allow(unknown_lints, clippy::io_other_error)]
+pub(super) fn serve(state: Arc<Mutex<State>>) -> io::Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    listener.set_nonblocking(true)?;
+    let addr = listener.local_addr()?;
+
+    let make_service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+    });
+    let server = Server::from_tcp(listener)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+        .serve(make_service);
+
+    let task = tokio::spawn(async move {
+        if let Err(e) = server.await {
+            log::error!("mock REST server failed: {}", e);
+        }
+    });
+
+    Ok((addr, task))
+}
+
+async fn handle(
+    state: Arc<Mutex<State>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let method = req.method().clone();
+    let path = req.uri().path();
+    let path = path.strip_prefix("/api").unwrap_or(path).to_owned();
+    let query = req.uri().query().unwrap_or("").to_owned();
+    let headers = req.headers().clone();
+    let body = to_bytes(req.into_body())
+        .await
+        .map(|body| String::from_utf8_lossy(&body).into_owned())
+        .unwrap_or_default();
+
+    let response = state.lock().unwrap().respond(RecordedRequest {
+        method,
+        path,
+        query,
+        headers,
+        body,
+    });
+
+    Ok(Response::builder()
+        .status(response.status)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(response.body.to_string()))
+        .unwrap())
+}
+
+fn not_found(error: &str) -> MockResponse {
+    MockResponse::error(StatusCode::NOT_FOUND, error)
+}
+
+fn bad_request(error: &str) -> MockResponse {
+    MockResponse::error(StatusCode::BAD_REQUEST, error)
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (escaped, bytes[i]) {
+            (Some(byte), _) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (None, b'+') => decoded.push(b' '),
+            (None, byte) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn query_params(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            let key = percent_decode(parts.next().unwrap_or(""));
+            let value = percent_decode(parts.next().unwrap_or(""));
+            (key, value)
+        })
+        .collect()
+}
+
+fn time_param(params: &HashMap<String, String>, key: &str) -> Option<DateTime<Utc>> {
+    let seconds = params.get(key)?.parse::<f64>().ok()?;
+    Utc.timestamp_opt(seconds as i64, 0).single()
+}
+
+fn limit_param(params: &HashMap<String, String>, default: usize) -> usize {
+    params
+        .get("limit")
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(default)
+}
+
+fn in_window(
+    time: DateTime<Utc>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> bool {
+    unset_or(start, |start| time >= start) && unset_or(end, |end| time <= end)
+}
+
+/// Whether an optional filter of a request is unset or accepts a value
+fn unset_or<T>(filter: Option<T>, accepts: impl FnOnce(T) -> bool) -> bool {
+    match filter {
+        Some(filter) => accepts(filter),
+        None => true,
+    }
+}
+
+fn field<T: DeserializeOwned>(body: &Value, key: &str) -> Option<T> {
+    body.get(key)
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+}
+
+impl State {
+    fn respond(&mut self, req: RecordedRequest) -> MockResponse {
+        self.requests.push(req.clone());
+
+        if let Some(i) = self
+            .routes
+            .iter()
+            .position(|route| route.method == req.method && route.path == req.path)
+        {
+            let response = self.routes[i].response.clone();
+            if self.routes[i].once {
+                self.routes.remove(i);
+            }
+            return response;
+        }
+
+        let params = query_params(&req.query);
+        let body: Value = serde_json::from_str(&req.body).unwrap_or(Value::Null);
+        let segments: Vec<&str> = req.path.trim_matches('/').split('/').collect();
+
+        let public = match (&req.method, segments.as_slice()) {
+            (&Method::GET, ["markets"]) => Some(MockResponse::success(&self.markets)),
+            (&Method::GET, ["markets", ..]) => Some(self.market_route(&req.path, &params)),
+            (&Method::GET, ["futures"]) => Some(MockResponse::success(&self.futures)),
+            (&Method::GET, ["futures", name]) => Some(
+                self.futures
+                    .iter()
+                    .find(|future| future.name == *name)
+                    .map(MockResponse::success)
+                    .unwrap_or_else(|| not_found(&format!("No such future: {}", name))),
+            ),
+            _ => None,
+        };
+        if let Some(response) = public {
+            return response;
+        }
+
+        if let Err(response) = self.authenticate(&req) {
+            return response;
+        }
+
+        match (&req.method, segments.as_slice()) {
+            (&Method::GET, ["positions"]) => MockResponse::success(&self.positions),
+            (&Method::GET, ["fills"]) => self.get_fills(&params),
+            (&Method::GET, ["orders"]) => {
+                let market = params.get("market");
+                let orders: Vec<&OrderInfo> = self
+                    .orders
+                    .values()
+                    .filter(|order| order.status != OrderStatus::Closed)
+                    .filter(|order| unset_or(market, |market| &order.market == market))
+                    .collect();
+                MockResponse::success(orders)
+            }
+            (&Method::GET, ["orders", "history"]) => self.get_order_history(&params),
+            (&Method::POST, ["orders"]) => self.place_order(&body),
+            (&Method::DELETE, ["orders"]) => self.cancel_all_orders(&body),
+            (&Method::GET, ["orders", "by_client_id", client_id]) => {
+                match self.find_by_client_id(client_id) {
+                    Some(id) => MockResponse::success(&self.orders[&id]),
+                    None => not_found("Order not found"),
+                }
+            }
+            (&Method::DELETE, ["orders", "by_client_id", client_id]) => {
+                match self.find_by_client_id(client_id) {
+                    Some(id) => self.cancel_order(id),
+                    None => not_found("Order not found"),
+                }
+            }
+            (&Method::POST, ["orders", "by_client_id", client_id, "modify"]) => {
+                match self.find_by_client_id(client_id) {
+                    Some(id) => self.modify_order(id, &body),
+                    None => not_found("Order not found"),
+                }
+            }
+            (&Method::GET, ["orders", id]) => match id.parse::<Id>() {
+                Ok(id) if self.orders.contains_key(&id) => MockResponse::success(&self.orders[&id]),
+                _ => not_found("Order not found"),
+            },
+            (&Method::DELETE, ["orders", id]) => match id.parse() {
+                Ok(id) => self.cancel_order(id),
+                Err(_) => not_found("Order not found"),
+            },
+            (&Method::POST, ["orders", id, "modify"]) => match id.parse() {
+                Ok(id) => self.modify_order(id, &body),
+                Err(_) => not_found("Order not found"),
+            },
+            _ => not_found("Not Found"),
+        }
+    }
+
+    /// Checks the authentication headers the same way FTX does.
+    fn authenticate(&self, req: &RecordedRequest) -> Result<(), MockResponse> {
+        let header = |suffix: &str| {
+            req.headers
+                .get(format!("{}-{}", HEADER_PREFIX, suffix))
+                .and_then(|value| value.to_str().ok())
+        };
+        let not_logged_in = || MockResponse::error(StatusCode::UNAUTHORIZED, "Not logged in");
+
+        let key = header("KEY").ok_or_else(not_logged_in)?;
+        if let Some((expected_key, secret)) = &self.credentials {
+            let timestamp = header("TS").ok_or_else(not_logged_in)?;
+            let sign = header("SIGN").ok_or_else(not_logged_in)?;
+
+            let mut path = format!("/api{}", req.path);
+            if !req.query.is_empty() {
+                path.push('?');
+                path.push_str(&req.query);
+            }
+            let payload = format!("{}{}{}{}", timestamp, req.method, path, req.body);
+            let expected_sign = hex::encode(HMAC::mac(payload.as_bytes(), secret.as_bytes()));
+
+            if key != expected_key {
+                return Err(MockResponse::error(
+                    StatusCode::UNAUTHORIZED,
+                    "Not logged in: Invalid API key",
+                ));
+            }
+            if sign != expected_sign {
+                return Err(MockResponse::error(
+                    StatusCode::UNAUTHORIZED,
+                    "Not logged in: Invalid signature",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn market_route(&self, path: &str, params: &HashMap<String, String>) -> MockResponse {
+        let name = path.trim_start_matches("/markets/");
+
+        if let Some(name) = name.strip_suffix("/orderbook") {
+            let depth = params
+                .get("depth")
+                .and_then(|depth| depth.parse().ok())
+                .unwrap_or(20);
+            return match self.orderbooks.get(name) {
+                Some(orderbook) => MockResponse::success(json!({
+                    "bids": orderbook.bids.iter().rev().take(depth).collect::<Vec<_>>(),
+                    "asks": orderbook.asks.iter().take(depth).collect::<Vec<_>>(),
+                })),
+                None => not_found(&format!("No such market: {}", name)),
+            };
+        }
+
+        if let Some(name) = name.strip_suffix("/trades") {
+            let start = time_param(params, "start_time");
+            let end = time_param(params, "end_time");
+            let mut trades: Vec<&Trade> = self
+                .trades
+                .get(name)
+                .into_iter()
+                .flatten()
+                .filter(|trade| in_window(trade.time, start, end))
+                .collect();
+            // Newest trades first, like FTX
+            trades.sort_by(|a, b| b.time.cmp(&a.time).then(b.id.cmp(&a.id)));
+            trades.truncate(limit_param(params, 100));
+            return MockResponse::success(trades);
+        }
+
+        if let Some(name) = name.strip_suffix("/candles") {
+            let resolution = params
+                .get("resolution")
+                .and_then(|resolution| resolution.parse().ok())
+                .unwrap_or(0);
+            let start = time_param(params, "start_time");
+            let end = time_param(params, "end_time");
+            let mut candles: Vec<_> = self
+                .candles
+                .get(&(name.to_owned(), resolution))
+                .into_iter()
+                .flatten()
+                .filter(|candle| in_window(candle.start_time, start, end))
+                .collect();
+            // Oldest candles first, but only the latest within the window
+            candles.sort_by_key(|candle| candle.start_time);
+            let limit = limit_param(params, 1501);
+            if candles.len() > limit {
+                candles.drain(..candles.len() - limit);
+            }
+            return MockResponse::success(candles);
+        }
+
+        match self.markets.iter().find(|market| market.name == name) {
+            Some(market) => MockResponse::success(market),
+            None => not_found(&format!("No such market: {}", name)),
+        }
+    }
+
+    fn get_fills(&self, params: &HashMap<String, String>) -> MockResponse {
+        let market = params.get("market");
+        let order_id = params.get("orderId").and_then(|id| id.parse::<Id>().ok());
+        let start = time_param(params, "start_time");
+        let end = time_param(params, "end_time");
+
+        let mut fills: Vec<&Fill> = self
+            .fills
+            .iter()
+            .filter(|fill| unset_or(market, |market| fill.market.as_ref() == Some(market)))
+            .filter(|fill| unset_or(order_id, |id| fill.order_id == Some(id)))
+            .filter(|fill| in_window(fill.time, start, end))
+            .collect();
+        fills.sort_by(|a, b| b.time.cmp(&a.time).then(b.id.cmp(&a.id)));
+        fills.truncate(limit_param(params, 100));
+        MockResponse::success(fills)
+    }
+
+    fn get_order_history(&self, params: &HashMap<String, String>) -> MockResponse {
+        let market = params.get("market");
+        let start = time_param(params, "start_time");
+        let end = time_param(params, "end_time");
+
+        let mut orders: Vec<&OrderInfo> = self
+            .orders
+            .values()
+            .filter(|order| unset_or(market, |market| &order.market == market))
+            .filter(|order| in_window(order.created_at, start, end))
+            .collect();
+        orders.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        orders.truncate(limit_param(params, 100));
+        MockResponse::success(orders)
+    }
+
+    fn find_by_client_id(&self, client_id: &str) -> Option<Id> {
+        // Prefer the most recent order with this client ID
+        self.orders
+            .values()
+            .rev()
+            .find(|order| order.client_id.as_deref() == Some(client_id))
+            .map(|order| order.id)
+    }
+
+    fn place_order(&mut self, body: &Value) -> MockResponse {
+        let market: String = match field(body, "market") {
+            Some(market) => market,
+            None => return bad_request("Missing parameter market"),
+        };
+        if !self.markets.is_empty() && !self.markets.iter().any(|m| m.name == market) {
+            return not_found(&format!("No such market: {}", market));
+        }
+        let (side, r#type, size): (Side, OrderType, Decimal) = match (
+            field(body, "side"),
+            field(body, "type"),
+            field(body, "size"),
+        ) {
+            (Some(side), Some(r#type), Some(size)) => (side, r#type, size),
+            _ => return bad_request("Missing parameter"),
+        };
+        let price: Option<Decimal> = field(body, "price");
+        if r#type == OrderType::Limit && price.is_none() {
+            return bad_request("Missing parameter price");
+        }
+        let client_id: Option<String> = field(body, "clientId");
+        if let Some(client_id) = &client_id {
+            let duplicate = self.orders.values().any(|order| {
+                order.status != OrderStatus::Closed && order.client_id.as_ref() == Some(client_id)
+            });
+            if duplicate {
+                return bad_request("Duplicate client order ID");
+            }
+        }
+
+        let order = OrderInfo {
+            id: self.next_id(),
+            market,
+            future: None,
+            r#type,
+            side,
+            price,
+            size,
+            reduce_only: field(body, "reduceOnly"),
+            ioc: field(body, "ioc"),
+            post_only: field(body, "postOnly"),
+            status: OrderStatus::New,
+            filled_size: Some(Decimal::ZERO),
+            remaining_size: Some(size),
+            avg_fill_price: None,
+            liquidation: Some(false),
+            created_at: Utc::now(),
+            client_id,
+            retry_until_filled: None,
+            trigger_price: None,
+            order_price: None,
+            triggered_at: None,
+            error: None,
+        };
+
+        let response = MockResponse::success(&order);
+        self.process_order(order);
+        response
+    }
+
+    /// Simulates the matching engine processing a queued order, publishing
+    /// the outcome on the fills and orders channels.
+    fn process_order(&mut self, mut order: OrderInfo) {
+        let behavior = self
+            .scripted_behaviors
+            .pop_front()
+            .unwrap_or(self.order_behavior);
+
+        match behavior {
+            OrderBehavior::Rest => {
+                // Over websockets, active orders are reported as new
+                self.publish_order(&order);
+                order.status = OrderStatus::Open;
+            }
+            OrderBehavior::Fill => {
+                let price = self.fill_price(&order);
+                order.status = OrderStatus::Closed;
+                order.filled_size = Some(order.size);
+                order.remaining_size = Some(Decimal::ZERO);
+                order.avg_fill_price = Some(price);
+
+                let fill = Fill {
+                    id: self.next_id(),
+                    market: Some(order.market.clone()),
+                    future: None,
+                    base_currency: None,
+                    quote_currency: None,
+                    r#type: "order".to_owned(),
+                    side: order.side,
+                    price,
+                    size: order.size,
+                    order_id: Some(order.id),
+                    trade_id: Some(self.next_id()),
+                    time: Utc::now(),
+                    fee: Decimal::ZERO,
+                    fee_rate: Decimal::ZERO,
+                    fee_currency: "USD".to_owned(),
+                    liquidity: Liquidity::Taker,
+                };
+                self.publish(&Channel::Fills, Type::Update, Some(json!(fill)));
+                self.fills.push(fill);
+                self.publish_order(&order);
+            }
+            OrderBehavior::Reject => {
+                order.status = OrderStatus::Closed;
+                order.remaining_size = Some(Decimal::ZERO);
+                self.publish_order(&order);
+            }
+        }
+
+        self.orders.insert(order.id, order);
+    }
+
+    /// The limit price, or the best price on the other side of the book
+    /// for market orders.
+    fn fill_price(&self, order: &OrderInfo) -> Decimal {
+        order
+            .price
+            .or_else(|| {
+                let orderbook = self.orderbooks.get(&order.market)?;
+                match order.side {
+                    Side::Buy => orderbook.ask_price().copied(),
+                    Side::Sell => orderbook.bid_price().copied(),
+                }
+            })
+            .or_else(|| {
+                self.markets
+                    .iter()
+                    .find(|market| market.name == order.market)
+                    .and_then(|market| market.price)
+            })
+            .unwrap_or_default()
+    }
+
+    fn publish_order(&mut self, order: &OrderInfo) {
+        self.publish(&Channel::Orders, Type::Update, Some(json!(order)));
+    }
+
+    fn close_order(&mut self, id: Id) -> Result<(), MockResponse> {
+        let order = self
+            .orders
+            .get_mut(&id)
+            .ok_or_else(|| not_found("Order not found"))?;
+        if order.status == OrderStatus::Closed {
+            return Err(bad_request("Order already closed"));
+        }
+        order.status = OrderStatus::Closed;
+        order.remaining_size = Some(Decimal::ZERO);
+        let order = order.clone();
+        self.publish_order(&order);
+        Ok(())
+    }
+
+    fn cancel_order(&mut self, id: Id) -> MockResponse {
+        match self.close_order(id) {
+            Ok(()) => MockResponse::success("Order queued for cancellation"),
+            Err(response) => response,
+        }
+    }
+
+    fn modify_order(&mut self, id: Id, body: &Value) -> MockResponse {
+        let old = match self.orders.get(&id) {
+            Some(order) => order.clone(),
+            None => return not_found("Order not found"),
+        };
+        // Modifying is implemented by FTX as cancelling and placing a new order
+        if let Err(response) = self.close_order(id) {
+            return response;
+        }
+
+        let size = field(body, "size")
+            .or(old.remaining_size)
+            .unwrap_or(old.size);
+        let order = OrderInfo {
+            id: self.next_id(),
+            price: field(body, "price").or(old.price),
+            size,
+            status: OrderStatus::New,
+            filled_size: Some(Decimal::ZERO),
+            remaining_size: Some(size),
+            avg_fill_price: None,
+            created_at: Utc::now(),
+            client_id: field(body, "clientId").or(old.client_id),
+            ..old
+        };
+
+        let response = MockResponse::success(&order);
+        self.process_order(order);
+        response
+    }
+
+    fn cancel_all_orders(&mut self, body: &Value) -> MockResponse {
+        let market: Option<String> = field(body, "market");
+        let side: Option<Side> = field(body, "side");
+        let conditional_orders_only: bool = field(body, "conditionalOrdersOnly").unwrap_or(false);
+        let limit_orders_only: bool = field(body, "limitOrdersOnly").unwrap_or(false);
+
+        let ids: Vec<Id> = self
+            .orders
+            .values()
+            .filter(|order| order.status != OrderStatus::Closed)
+            .filter(|_| !conditional_orders_only)
+            .filter(|order| unset_or(market.as_ref(), |market| &order.market == market))
+            .filter(|order| unset_or(side, |side| order.side == side))
+            .filter(|order| !limit_orders_only || order.r#type == OrderType::Limit)
+            .map(|order| order.id)
+            .collect();
+        for id in ids {
+            let _ = self.close_order(id);
+        }
+
+        MockResponse::success("Orders queued for cancellation")
+    }
+}
@@ -0,0 +1,333 @@
+use super::{Client, State};
+use crate::rest::Market;
+use crate::ws::{Channel, GroupedOrderbook, Orderbook};
+use chrono::Utc;
+use futures::{SinkExt, StreamExt};
+use hmac_sha256::HMAC;
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+pub(super) async fn serve(state: Arc<Mutex<State>>) -> io::Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let task = tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(connection(stream, state.clone()));
+        }
+    });
+
+    Ok((addr, task))
+}
+
+async fn connection(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let stream = match accept_async(stream).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            log::error!("mock websocket handshake failed: {}", e);
+            return;
+        }
+    };
+    let (mut sink, mut source) = stream.split();
+    let (sender, mut receiver) = unbounded_channel();
+
+    let id = {
+        let mut state = state.lock().unwrap();
+        state.next_client += 1;
+        let id = state.next_client;
+        state.clients.insert(
+            id,
+            Client {
+                sender,
+                channels: Vec::new(),
+                authenticated: false,
+            },
+        );
+        id
+    };
+
+    loop {
+        tokio::select! {
+            outgoing = receiver.recv() => match outgoing {
+                Some(message) => {
+                    if sink.send(message).await.is_err() {
+                        break;
+                    }
+                }
+                // The client was removed from the state, e.g. by `disconnect_all`
+                None => break,
+            },
+            incoming = source.next() => match incoming {
+                Some(Ok(Message::Text(text))) => state.lock().unwrap().handle_message(id, &text),
+                Some(Ok(_)) => {}
+                _ => break,
+            },
+        }
+    }
+
+    state.lock().unwrap().clients.remove(&id);
+    let _ = sink.close().await;
+    // Wait for the client to acknowledge the close, since closing a socket
+    // with unread messages, e.g. pings, resets the connection instead
+    let drain = async { while let Some(Ok(_)) = source.next().await {} };
+    let _ = tokio::time::timeout(Duration::from_secs(1), drain).await;
+}
+
+/// The name of the channel and the market it belongs to, as used in
+/// messages of the websocket API.
+pub(super) fn channel_name(channel: &Channel) -> (&'static str, Option<&str>) {
+    match channel {
+        Channel::Orderbook(symbol) => ("orderbook", Some(symbol)),
+        Channel::Trades(symbol) => ("trades", Some(symbol)),
+        Channel::Ticker(symbol) => ("ticker", Some(symbol)),
+        Channel::Fills => ("fills", None),
+        Channel::Orders => ("orders", None),
+        Channel::Markets => ("markets", None),
+        Channel::GroupedOrderbook { market, .. } => ("orderbookGrouped", Some(market)),
+    }
+}
+
+fn parse_channel(message: &Value) -> Option<Channel> {
+    let market = message["market"].as_str().unwrap_or("").to_owned();
+    match message["channel"].as_str()? {
+        "orderbook" => Some(Channel::Orderbook(market)),
+        "trades" => Some(Channel::Trades(market)),
+        "ticker" => Some(Channel::Ticker(market)),
+        "fills" => Some(Channel::Fills),
+        "orders" => Some(Channel::Orders),
+        "markets" => Some(Channel::Markets),
+        "orderbookGrouped" => Some(Channel::GroupedOrderbook {
+            market,
+            grouping: message["grouping"].to_string().parse().ok()?,
+        }),
+        _ => None,
+    }
+}
+
+/// Serializes orderbook data the way FTX does, with the checksum of the
+/// book after applying it.
+pub(super) fn orderbook_data(
+    orderbook: &Orderbook,
+    action: &str,
+    bids: &[(Decimal, Decimal)],
+    asks: &[(Decimal, Decimal)],
+) -> Value {
+    let time = Utc::now();
+    json!({
+        "action": action,
+        "bids": bids,
+        "asks": asks,
+        "checksum": orderbook.checksum(),
+        "time": time.timestamp_millis() as f64 / 1000.0,
+    })
+}
+
+/// Serializes the levels of a grouped orderbook that differ from `previous`
+/// the way FTX does, with a size of zero for removed buckets.
+pub(super) fn grouped_orderbook_data(
+    orderbook: &GroupedOrderbook,
+    previous: &GroupedOrderbook,
+) -> Value {
+    let diff = |levels: &BTreeMap<Decimal, Decimal>, previous: &BTreeMap<Decimal, Decimal>| {
+        let mut diff: Vec<_> = levels
+            .iter()
+            .filter(|(price, size)| previous.get(price) != Some(size))
+            .map(|(price, size)| (*price, *size))
+            .collect();
+        diff.extend(
+            previous
+                .keys()
+                .filter(|price| !levels.contains_key(price))
+                .map(|price| (*price, Decimal::ZERO)),
+        );
+        diff
+    };
+    let mut bids = diff(&orderbook.bids, &previous.bids);
+    bids.sort_by_key(|(price, _)| Reverse(*price));
+    let mut asks = diff(&orderbook.asks, &previous.asks);
+    asks.sort_by_key(|(price, _)| *price);
+
+    json!({
+        "bids": bids,
+        "asks": asks,
+    })
+}
+
+/// Serializes market definitions the way the markets channel does, keyed
+/// by market name.
+pub(super) fn markets_data(markets: &[Market], action: &str) -> Value {
+    let data: serde_json::Map<_, _> = markets
+        .iter()
+        .map(|market| (market.name.clone(), json!(market)))
+        .collect();
+    json!({
+        "action": action,
+        "data": data,
+    })
+}
+
+impl State {
+    fn send(&self, id: usize, message: Value) {
+        if let Some(client) = self.clients.get(&id) {
+            let _ = client.sender.send(Message::Text(message.to_string()));
+        }
+    }
+
+    fn handle_message(&mut self, id: usize, text: &str) {
+        let message: Value = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(_) => {
+                self.send(
+                    id,
+                    json!({"type": "error", "code": 400, "msg": "Invalid JSON"}),
+                );
+                return;
+            }
+        };
+
+        match message["op"].as_str() {
+            Some("ping") if self.ignore_pings => {}
+            Some("ping") => self.send(id, json!({"type": "pong"})),
+            Some("login") => self.login(id, &message["args"]),
+            Some(op @ "subscribe") | Some(op @ "unsubscribe") => {
+                let channel = match parse_channel(&message) {
+                    Some(channel) => channel,
+                    None => {
+                        self.send(
+                            id,
+                            json!({"type": "error", "code": 404, "msg": "Channel not found"}),
+                        );
+                        return;
+                    }
+                };
+                if op == "subscribe" {
+                    self.subscribe(id, channel);
+                } else {
+                    self.unsubscribe(id, channel);
+                }
+            }
+            _ => self.send(
+                id,
+                json!({"type": "error", "code": 400, "msg": "Invalid op"}),
+            ),
+        }
+    }
+
+    fn login(&mut self, id: usize, args: &Value) {
+        let valid = match &self.credentials {
+            Some((key, secret)) => {
+                let payload = format!("{}websocket_login", args["time"]);
+                let sign = hex::encode(HMAC::mac(payload.as_bytes(), secret.as_bytes()));
+                args["key"].as_str() == Some(key) && args["sign"].as_str() == Some(&sign)
+            }
+            None => args["key"].is_string(),
+        };
+
+        if valid {
+            if let Some(client) = self.clients.get_mut(&id) {
+                client.authenticated = true;
+            }
+        } else {
+            self.send(
+                id,
+                json!({"type": "error", "code": 400, "msg": "Invalid login credentials"}),
+            );
+        }
+    }
+
+    fn subscribe(&mut self, id: usize, channel: Channel) {
+        let (name, market) = channel_name(&channel);
+        let authenticated = match self.clients.get(&id) {
+            Some(client) => client.authenticated,
+            None => return,
+        };
+        if matches!(channel, Channel::Fills | Channel::Orders) && !authenticated {
+            self.send(
+                id,
+                json!({"type": "error", "code": 400, "msg": "Not logged in"}),
+            );
+            return;
+        }
+
+        self.send(
+            id,
+            json!({"type": "subscribed", "channel": name, "market": market}),
+        );
+
+        if let Channel::Orderbook(symbol) = &channel {
+            let empty = Orderbook::new(symbol.clone());
+            let orderbook = self.orderbooks.get(symbol).unwrap_or(&empty);
+            let bids: Vec<_> = orderbook.bids.iter().rev().map(|(p, s)| (*p, *s)).collect();
+            let asks: Vec<_> = orderbook.asks.iter().map(|(p, s)| (*p, *s)).collect();
+            let data = orderbook_data(orderbook, "partial", &bids, &asks);
+            self.send(
+                id,
+                json!({
+                    "channel": name,
+                    "market": market,
+                    "type": "partial",
+                    "data": data,
+                }),
+            );
+        }
+
+        if let Channel::GroupedOrderbook {
+            market: symbol,
+            grouping,
+        } = &channel
+        {
+            let empty = Orderbook::new(symbol.clone());
+            let orderbook = self.orderbooks.get(symbol).unwrap_or(&empty);
+            let grouped = GroupedOrderbook::from_orderbook(orderbook, *grouping);
+            let previous = GroupedOrderbook::new(symbol.clone(), *grouping);
+            let data = grouped_orderbook_data(&grouped, &previous);
+            self.send(
+                id,
+                json!({
+                    "channel": name,
+                    "market": market,
+                    "type": "partial",
+                    "data": data,
+                }),
+            );
+        }
+
+        if channel == Channel::Markets {
+            let data = markets_data(&self.markets, "partial");
+            self.send(
+                id,
+                json!({
+                    "channel": name,
+                    "market": market,
+                    "type": "partial",
+                    "data": data,
+                }),
+            );
+        }
+
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.channels.push(channel);
+        }
+    }
+
+    fn unsubscribe(&mut self, id: usize, channel: Channel) {
+        let (name, market) = channel_name(&channel);
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.channels.retain(|c| c != &channel);
+        }
+        self.send(
+            id,
+            json!({"type": "unsubscribed", "channel": name, "market": market}),
+        );
+    }
+}
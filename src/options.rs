@@ -1,85 +1,225 @@
-use std::env::var;
+use std::{
+    borrow::Cow,
+    env::{var, VarError},
+    time::Duration,
+};
 
 use const_format::concatcp;
+use rand::Rng;
+use thiserror::Error;
 
 #[derive(Debug, Clone)]
 pub enum Endpoint {
     Com,
     Us,
+    /// Any other server speaking the FTX API, e.g. a local mock exchange or
+    /// a staging environment.
+    Custom {
+        /// Base URL of the REST API, e.g. `http://127.0.0.1:8080/api`
+        rest: String,
+        /// URL of the websocket API, e.g. `ws://127.0.0.1:8081/ws`
+        ws: String,
+        /// Prefix of the authentication headers without the trailing dash,
+        /// e.g. `FTX` results in `FTX-KEY`, `FTX-SIGN`, ...
+        header_prefix: String,
+    },
+}
+
+// `#[default]` on variants requires a newer Rust than the supported 1.54
+#[allow(unknown_lints, clippy::derivable_impls)]
+impl Default for Endpoint {
+    fn default() -> Self {
+        Endpoint::Com
+    }
 }
 
 const ENDPOINT_HDR_PREFIX_COM: &str = "FTX-";
 const ENDPOINT_HDR_PREFIX_US: &str = "FTXUS-";
 
 impl Endpoint {
-    pub const fn ws(&self) -> &'static str {
+    pub fn ws(&self) -> &str {
         match self {
             Endpoint::Com => "wss://ftx.com/ws",
             Endpoint::Us => "wss://ftx.us/ws",
+            Endpoint::Custom { ws, .. } => ws,
         }
     }
 
-    pub const fn rest(&self) -> &'static str {
+    pub fn rest(&self) -> &str {
         match self {
             Endpoint::Com => "https://ftx.com/api",
             Endpoint::Us => "https://ftx.us/api",
+            Endpoint::Custom { rest, .. } => rest,
         }
     }
     #[cfg(feature = "optimized-access")]
-    pub const fn optimized_access_rest(&self) -> &'static str {
+    pub fn optimized_access_rest(&self) -> &str {
         match self {
             Endpoint::Com => "https://api.ftx.com/api",
             Endpoint::Us => "https://ftx.us/api",
+            Endpoint::Custom { rest, .. } => rest,
         }
     }
-    pub const fn header_prefix(&self) -> &'static str {
+    pub fn header_prefix(&self) -> &str {
         match self {
             Endpoint::Com => "FTX",
             Endpoint::Us => "FTXUS",
+            Endpoint::Custom { header_prefix, .. } => header_prefix,
         }
     }
 
-    pub const fn timestamp_header(&self) -> &'static str {
+    pub fn timestamp_header(&self) -> Cow<'_, str> {
         match self {
-            Endpoint::Com => concatcp!(ENDPOINT_HDR_PREFIX_COM, "TS"),
-            Endpoint::Us => concatcp!(ENDPOINT_HDR_PREFIX_US, "TS"),
+            Endpoint::Com => Cow::Borrowed(concatcp!(ENDPOINT_HDR_PREFIX_COM, "TS")),
+            Endpoint::Us => Cow::Borrowed(concatcp!(ENDPOINT_HDR_PREFIX_US, "TS")),
+            Endpoint::Custom { header_prefix, .. } => Cow::Owned(format!("{}-TS", header_prefix)),
         }
     }
 
-    pub const fn sign_header(&self) -> &'static str {
+    pub fn sign_header(&self) -> Cow<'_, str> {
         match self {
-            Endpoint::Com => concatcp!(ENDPOINT_HDR_PREFIX_COM, "SIGN"),
-            Endpoint::Us => concatcp!(ENDPOINT_HDR_PREFIX_US, "SIGN"),
+            Endpoint::Com => Cow::Borrowed(concatcp!(ENDPOINT_HDR_PREFIX_COM, "SIGN")),
+            Endpoint::Us => Cow::Borrowed(concatcp!(ENDPOINT_HDR_PREFIX_US, "SIGN")),
+            Endpoint::Custom { header_prefix, .. } => Cow::Owned(format!("{}-SIGN", header_prefix)),
         }
     }
 
-    pub const fn subaccount_header(&self) -> &'static str {
+    pub fn subaccount_header(&self) -> Cow<'_, str> {
         match self {
-            Endpoint::Com => concatcp!(ENDPOINT_HDR_PREFIX_COM, "SUBACCOUNT"),
-            Endpoint::Us => concatcp!(ENDPOINT_HDR_PREFIX_US, "SUBACCOUNT"),
+            Endpoint::Com => Cow::Borrowed(concatcp!(ENDPOINT_HDR_PREFIX_COM, "SUBACCOUNT")),
+            Endpoint::Us => Cow::Borrowed(concatcp!(ENDPOINT_HDR_PREFIX_US, "SUBACCOUNT")),
+            Endpoint::Custom { header_prefix, .. } => {
+                Cow::Owned(format!("{}-SUBACCOUNT", header_prefix))
+            }
         }
     }
 
-    pub const fn key_header(&self) -> &'static str {
+    pub fn key_header(&self) -> Cow<'_, str> {
         match self {
-            Endpoint::Com => concatcp!(ENDPOINT_HDR_PREFIX_COM, "KEY"),
-            Endpoint::Us => concatcp!(ENDPOINT_HDR_PREFIX_US, "KEY"),
+            Endpoint::Com => Cow::Borrowed(concatcp!(ENDPOINT_HDR_PREFIX_COM, "KEY")),
+            Endpoint::Us => Cow::Borrowed(concatcp!(ENDPOINT_HDR_PREFIX_US, "KEY")),
+            Endpoint::Custom { header_prefix, .. } => Cow::Owned(format!("{}-KEY", header_prefix)),
         }
     }
 }
 
-impl Default for Endpoint {
+/// Determines how `Rest` retries requests that failed with a transient
+/// error, e.g. because of rate limiting or a server error. Only requests with
+/// an idempotent method, or that are marked as `Request::RETRY_SAFE`, are
+/// retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the initial attempt
+    pub max_retries: u32,
+    /// Backoff before the first retry, doubled with every further retry
+    pub initial_backoff: Duration,
+    /// Upper bound of the backoff
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
     fn default() -> Self {
-        Endpoint::Com
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The time to wait before the given retry, starting at zero. The
+    /// exponential backoff is jittered to a random duration between half and
+    /// all of it, so that concurrent clients do not retry in lockstep.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .checked_mul(2u32.saturating_pow(retry))
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff));
+        backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+/// A token bucket that allows bursts of up to `requests` requests, which
+/// are replenished evenly over `per`.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    /// Checks that both `requests` and `per` are positive.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.requests == 0 || self.per.is_zero() {
+            return Err(ConfigError::InvalidRateLimit(*self));
+        }
+        Ok(())
+    }
+}
+
+/// Limits the rate of requests sent by `Rest` and all of its clones.
+/// Requests wait until they may be sent instead of failing.
+#[derive(Debug, Clone)]
+pub struct RateLimits {
+    /// Limit of requests placing, modifying or cancelling orders
+    pub orders: RateLimit,
+    /// Limit of all other requests
+    pub other: RateLimit,
+}
+
+impl RateLimits {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.orders.validate()?;
+        self.other.validate()
+    }
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        let limit = RateLimit {
+            requests: 30,
+            per: Duration::from_secs(1),
+        };
+        Self {
+            orders: limit,
+            other: limit,
+        }
     }
 }
 
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("environment variable {0} is not defined")]
+    MissingEnvVar(&'static str),
+
+    #[error("environment variable {0} is not valid unicode")]
+    InvalidEnvVar(&'static str),
+
+    #[error("invalid value for header {0}")]
+    InvalidHeader(String),
+
+    #[error("invalid rate limit of {} requests per {:?}", .0.requests, .0.per)]
+    InvalidRateLimit(RateLimit),
+}
+
+fn env_var(key: &'static str) -> Result<String, ConfigError> {
+    var(key).map_err(|e| match e {
+        VarError::NotPresent => ConfigError::MissingEnvVar(key),
+        VarError::NotUnicode(_) => ConfigError::InvalidEnvVar(key),
+    })
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Options {
     pub endpoint: Endpoint,
     pub key: Option<String>,
     pub secret: Option<String>,
     pub subaccount: Option<String>,
+    /// Retry policy of `Rest`, which does not retry requests if `None`
+    pub retry_policy: Option<RetryPolicy>,
+    /// Rate limits of `Rest`, which does not limit requests if `None`
+    pub rate_limits: Option<RateLimits>,
 }
 
 impl Options {
@@ -90,22 +230,44 @@ impl Options {
         }
     }
 
+    /// Panics if the environment variables are missing, see
+    /// `Options::try_from_env`.
     pub fn from_env() -> Self {
-        Options::default()
-            .authenticate(
-                var("API_KEY").expect("API Key is not defined."),
-                var("API_SECRET").expect("API Secret is not defined."),
-            )
-            .subaccount_optional(var("SUBACCOUNT").ok())
+        Self::try_from_env().unwrap_or_else(|e| panic!("{}", e))
     }
 
+    /// Panics if the environment variables are missing, see
+    /// `Options::try_from_env_us`.
     pub fn from_env_us() -> Self {
-        Options::us()
-            .authenticate(
-                var("API_KEY").expect("API Key is not defined."),
-                var("API_SECRET").expect("API Secret is not defined."),
-            )
-            .subaccount_optional(var("SUBACCOUNT").ok())
+        Self::try_from_env_us().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Reads the API key and secret from `API_KEY` and `API_SECRET`, and the
+    /// optional subaccount from `SUBACCOUNT`.
+    pub fn try_from_env() -> Result<Self, ConfigError> {
+        Options::default().authenticate_from_env()
+    }
+
+    /// Like `Options::try_from_env`, but for FTX US.
+    pub fn try_from_env_us() -> Result<Self, ConfigError> {
+        Options::us().authenticate_from_env()
+    }
+
+    fn authenticate_from_env(self) -> Result<Self, ConfigError> {
+        let subaccount = match var("SUBACCOUNT") {
+            Ok(subaccount) => Some(subaccount),
+            Err(VarError::NotPresent) => None,
+            Err(VarError::NotUnicode(_)) => return Err(ConfigError::InvalidEnvVar("SUBACCOUNT")),
+        };
+        Ok(self
+            .authenticate(env_var("API_KEY")?, env_var("API_SECRET")?)
+            .subaccount_optional(subaccount))
+    }
+
+    #[must_use]
+    pub fn with_endpoint(mut self, endpoint: Endpoint) -> Self {
+        self.endpoint = endpoint;
+        self
     }
 
     #[must_use]
@@ -126,4 +288,43 @@ impl Options {
         self.subaccount = subaccount;
         self
     }
+
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    #[must_use]
+    pub fn with_rate_limits(mut self, rate_limits: RateLimits) -> Self {
+        self.rate_limits = Some(rate_limits);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_backoff() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+
+        for (retry, max) in [
+            (0, 100),
+            (1, 200),
+            (2, 400),
+            (3, 800),
+            (4, 1000),
+            (40, 1000),
+        ] {
+            let max = Duration::from_millis(max);
+            let backoff = policy.backoff(retry);
+            assert!(backoff >= max / 2 && backoff <= max, "{:?}", backoff);
+        }
+    }
 }
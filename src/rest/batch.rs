@@ -0,0 +1,66 @@
+use super::{CancelOrder, Error, Id, OrderInfo, PlaceOrder, Rest, Result};
+use futures::future::join_all;
+
+impl Rest {
+    /// Places the orders concurrently and returns the result of each order
+    /// in the same order as the input. Requests are throttled by the rate
+    /// limits of the options, if any.
+    pub async fn place_orders(&self, orders: Vec<PlaceOrder<'_>>) -> Vec<Result<OrderInfo>> {
+        join_all(orders.into_iter().map(|order| self.request(order))).await
+    }
+
+    /// Places the orders concurrently, like `place_orders`, but either all
+    /// of them or none. When any order fails, the orders that were placed
+    /// are cancelled again and `Error::BatchFailed` is returned for the
+    /// first failed order.
+    pub async fn place_orders_all_or_nothing(
+        &self,
+        orders: Vec<PlaceOrder<'_>>,
+    ) -> Result<Vec<OrderInfo>> {
+        let results = self.place_orders(orders).await;
+        if results.iter().all(Result::is_ok) {
+            return Ok(results.into_iter().flatten().collect());
+        }
+
+        let mut failure = None;
+        let mut placed = Vec::new();
+        for (index, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(order) => placed.push(order.id),
+                Err(e) if failure.is_none() => failure = Some((index, e)),
+                Err(_) => {}
+            }
+        }
+
+        let mut cancelled = Vec::new();
+        let mut remaining = Vec::new();
+        for (id, result) in placed.iter().zip(self.cancel_orders(placed.clone()).await) {
+            match result {
+                Ok(_) => cancelled.push(*id),
+                Err(e) => {
+                    log::error!("Could not cancel order {} of a failed batch: {}", id, e);
+                    remaining.push(*id);
+                }
+            }
+        }
+
+        let (index, source) = failure.expect("a failed order");
+        Err(Error::BatchFailed {
+            index,
+            source: Box::new(source),
+            cancelled,
+            remaining,
+        })
+    }
+
+    /// Cancels the orders concurrently and returns the result of each
+    /// cancellation in the same order as the input.
+    pub async fn cancel_orders(&self, order_ids: Vec<Id>) -> Vec<Result<String>> {
+        join_all(
+            order_ids
+                .into_iter()
+                .map(|id| self.request(CancelOrder::new(id))),
+        )
+        .await
+    }
+}
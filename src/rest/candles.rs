@@ -0,0 +1,200 @@
+use super::{Candle, Error, GetHistoricalPrices, Resolution, Rest, Result};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// The maximum number of candles FTX returns for a single request
+const MAX_CANDLES_PER_REQUEST: u32 = 1500;
+
+/// Downloads candles for arbitrary time ranges.
+///
+/// `GetHistoricalPrices` returns at most one page of candles. The service
+/// splits a time range into as many requests as needed, fills the gaps
+/// between the returned candles and resamples them into intervals which are
+/// not offered as a `Resolution`, e.g. ten minutes.
+///
+/// ```no_run
+/// # use ftx::{options::Options, rest::{CandleService, Resolution, Rest}};
+/// # use chrono::{Duration, Utc};
+/// # async fn example() -> ftx::rest::Result<()> {
+/// let candles = CandleService::new(Rest::new(Options::default()));
+/// let end = Utc::now();
+/// let ten_minutes = candles
+///     .fetch_resampled(
+///         "BTC-PERP",
+///         Resolution::FiveMinutes,
+///         std::time::Duration::from_secs(600),
+///         end - Duration::days(30),
+///         end,
+///     )
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CandleService {
+    rest: Rest,
+    chunk_size: u32,
+    fill_gaps: bool,
+}
+
+impl CandleService {
+    pub fn new(rest: Rest) -> Self {
+        Self {
+            rest,
+            chunk_size: MAX_CANDLES_PER_REQUEST,
+            fill_gaps: true,
+        }
+    }
+
+    /// The number of candles requested at once, at most 1500
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = chunk_size.clamp(1, MAX_CANDLES_PER_REQUEST);
+        self
+    }
+
+    /// Whether missing candles are filled in, enabled by default
+    #[must_use]
+    pub fn with_gap_filling(mut self, fill_gaps: bool) -> Self {
+        self.fill_gaps = fill_gaps;
+        self
+    }
+
+    /// Fetches the candles starting within `[start, end)`, oldest first.
+    /// `start` is rounded down to a multiple of the resolution, like FTX
+    /// aligns its candles.
+    pub async fn fetch(
+        &self,
+        market: &str,
+        resolution: Resolution,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Candle>> {
+        let seconds = i64::from(resolution.get_seconds());
+        let start = align(start, seconds);
+        let chunk = Duration::seconds(seconds * i64::from(self.chunk_size));
+
+        let mut candles = BTreeMap::new();
+        let mut chunk_start = start;
+        while chunk_start < end {
+            // The end time is inclusive, so it must not reach the next chunk,
+            // nor precede the start if less than a second is left
+            let chunk_end =
+                ((chunk_start + chunk).min(end) - Duration::seconds(1)).max(chunk_start);
+            let request = GetHistoricalPrices::new_paged(
+                market,
+                resolution,
+                Some(self.chunk_size),
+                Some(chunk_start),
+                Some(chunk_end),
+            );
+            for candle in self.rest.request(request).await? {
+                if candle.start_time >= start && candle.start_time < end {
+                    candles.insert(candle.start_time, candle);
+                }
+            }
+            chunk_start += chunk;
+        }
+
+        let candles: Vec<_> = candles.into_values().collect();
+        Ok(if self.fill_gaps {
+            fill_gaps(&candles, Duration::seconds(seconds))
+        } else {
+            candles
+        })
+    }
+
+    /// Fetches candles at `resolution` and resamples them into `interval`,
+    /// which needs to be a multiple of the resolution.
+    // `u64::is_multiple_of` requires a newer Rust than the supported 1.54
+    #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+    pub async fn fetch_resampled(
+        &self,
+        market: &str,
+        resolution: Resolution,
+        interval: std::time::Duration,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Candle>> {
+        let resolution_seconds = u64::from(resolution.get_seconds());
+        let interval_seconds = interval.as_secs();
+        if interval_seconds == 0 || interval_seconds % resolution_seconds != 0 {
+            return Err(Error::InvalidCandleInterval {
+                interval: interval_seconds,
+                resolution: resolution.get_seconds(),
+            });
+        }
+
+        let start = align(start, interval_seconds as i64);
+        let candles = self.fetch(market, resolution, start, end).await?;
+        Ok(resample(
+            &candles,
+            Duration::seconds(interval_seconds as i64),
+        ))
+    }
+}
+
+/// Rounds a time down to a multiple of `seconds` since the epoch.
+pub(crate) fn align(time: DateTime<Utc>, seconds: i64) -> DateTime<Utc> {
+    let timestamp = time.timestamp();
+    Utc.timestamp_opt(timestamp - timestamp.rem_euclid(seconds), 0)
+        .single()
+        .unwrap_or(time)
+}
+
+/// Inserts a candle for every `interval` missing between the first and the
+/// last candle. Missing candles had no trades, so they have no volume and
+/// open, close, high and low at the close of the previous candle.
+///
+/// The candles need to be sorted oldest first. Candles missing before the
+/// first or after the last candle are not filled in, since there is no
+/// price to fill them with.
+pub fn fill_gaps(candles: &[Candle], interval: Duration) -> Vec<Candle> {
+    let mut filled: Vec<Candle> = Vec::with_capacity(candles.len());
+    for candle in candles {
+        if let Some(&previous) = filled.last() {
+            let mut start_time = previous.start_time + interval;
+            while start_time < candle.start_time {
+                filled.push(Candle {
+                    open: previous.close,
+                    high: previous.close,
+                    low: previous.close,
+                    close: previous.close,
+                    volume: Decimal::ZERO,
+                    start_time,
+                });
+                start_time += interval;
+            }
+        }
+        filled.push(*candle);
+    }
+    filled
+}
+
+/// Combines candles into candles of a coarser `interval`, aligned to
+/// multiples of the interval since the epoch. The interval should be a
+/// multiple of the resolution of the candles, otherwise candles are
+/// attributed to the interval they start in.
+///
+/// The candles need to be sorted oldest first.
+pub fn resample(candles: &[Candle], interval: Duration) -> Vec<Candle> {
+    let seconds = interval.num_seconds().max(1);
+    let mut resampled: Vec<Candle> = Vec::new();
+    for candle in candles {
+        let start_time = align(candle.start_time, seconds);
+        match resampled.last_mut() {
+            Some(last) if last.start_time == start_time => {
+                last.high = last.high.max(candle.high);
+                last.low = last.low.min(candle.low);
+                last.close = candle.close;
+                last.volume += candle.volume;
+            }
+            _ => resampled.push(Candle {
+                start_time,
+                ..*candle
+            }),
+        }
+    }
+    resampled
+}
@@ -1,18 +1,69 @@
+use super::Id;
+use crate::options::ConfigError;
+use reqwest::StatusCode;
+use rust_decimal::Decimal;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("Api error: {0}")]
-    Api(String),
+    #[error("Rate limited ({0}): {1}")]
+    RateLimited(StatusCode, String),
+
+    #[error("Authentication failed ({0}): {1}")]
+    AuthFailed(StatusCode, String),
+
+    #[error("Insufficient margin ({0}): {1}")]
+    InsufficientMargin(StatusCode, String),
+
+    #[error("Order not found ({0}): {1}")]
+    OrderNotFound(StatusCode, String),
+
+    #[error("Order already closed ({0}): {1}")]
+    OrderAlreadyClosed(StatusCode, String),
+
+    #[error("Api error ({0}): {1}")]
+    Api(StatusCode, String),
 
     #[error("placing limit order requires price")]
     PlacingLimitOrderRequiresPrice,
 
+    #[error("unknown market: {0}")]
+    UnknownMarket(String),
+
+    #[error("price {price} is not a multiple of the price increment {increment}")]
+    InvalidPriceIncrement { price: Decimal, increment: Decimal },
+
+    #[error("size {size} is not a multiple of the size increment {increment}")]
+    InvalidSizeIncrement { size: Decimal, increment: Decimal },
+
+    #[error("size {size} is below the minimum size {min_size}")]
+    SizeBelowMinimum { size: Decimal, min_size: Decimal },
+
+    #[error("order {index} of the batch failed, {} orders were cancelled: {source}", cancelled.len())]
+    BatchFailed {
+        /// The position of the first failed order in the batch
+        index: usize,
+        source: Box<Error>,
+        /// The placed orders of the batch that were cancelled again
+        cancelled: Vec<Id>,
+        /// The placed orders of the batch that could not be cancelled
+        remaining: Vec<Id>,
+    },
+
+    #[error("candle interval of {interval}s is not a multiple of the resolution of {resolution}s")]
+    InvalidCandleInterval { interval: u64, resolution: u32 },
+
     #[error("endpoint requires auth but no secret configured")]
     NoSecretConfigured,
 
+    #[error("invalid header: {0}")]
+    InvalidHeader(String),
+
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
     #[error(transparent)]
     SerdeQs(#[from] serde_qs::Error),
 
@@ -25,3 +76,55 @@ pub enum Error {
     #[error(transparent)]
     SystemTime(#[from] std::time::SystemTimeError),
 }
+
+impl Error {
+    /// Classifies an error returned by the API by its HTTP status and
+    /// message.
+    pub fn from_response(status: StatusCode, message: String) -> Self {
+        let matches = |patterns: &[&str]| patterns.iter().any(|p| message.contains(p));
+
+        if status == StatusCode::TOO_MANY_REQUESTS
+            || matches(&["Please slow down", "Do not send more than"])
+        {
+            Error::RateLimited(status, message)
+        } else if status == StatusCode::UNAUTHORIZED || matches(&["Not logged in"]) {
+            Error::AuthFailed(status, message)
+        } else if matches(&["Not enough balances", "not have enough margin"]) {
+            Error::InsufficientMargin(status, message)
+        } else if matches(&["Order not found", "Order does not exist"]) {
+            Error::OrderNotFound(status, message)
+        } else if matches(&[
+            "Order already closed",
+            "Order already queued for cancellation",
+        ]) {
+            Error::OrderAlreadyClosed(status, message)
+        } else {
+            Error::Api(status, message)
+        }
+    }
+
+    /// The HTTP status of errors returned by the API
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::RateLimited(status, _)
+            | Error::AuthFailed(status, _)
+            | Error::InsufficientMargin(status, _)
+            | Error::OrderNotFound(status, _)
+            | Error::OrderAlreadyClosed(status, _)
+            | Error::Api(status, _) => Some(*status),
+            Error::Reqwest(e) => e.status(),
+            _ => None,
+        }
+    }
+
+    /// Whether the error is transient, i.e. sending the same request again
+    /// later may succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::RateLimited(..) => true,
+            Error::Api(status, _) => status.is_server_error(),
+            Error::Reqwest(e) => e.is_timeout() || e.is_connect(),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::options::{RateLimit, RateLimits};
+use std::sync::Mutex;
+use tokio::time::{sleep_until, Duration, Instant};
+
+/// Token buckets for order placement and other requests, shared by all
+/// clones of a `Rest`.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    orders: Bucket,
+    other: Bucket,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limits: &RateLimits) -> Self {
+        Self {
+            orders: Bucket::new(limits.orders),
+            other: Bucket::new(limits.other),
+        }
+    }
+
+    /// Waits until a request may be sent.
+    pub(crate) async fn acquire(&self, order_placement: bool) {
+        if order_placement {
+            self.orders.acquire().await
+        } else {
+            self.other.acquire().await
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    limit: RateLimit,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    /// Available tokens, negative if tokens have been reserved by waiting
+    /// requests
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            state: Mutex::new(BucketState {
+                tokens: limit.requests as f64,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    async fn acquire(&self) {
+        // Take a token right away, even if there is none left. Waiting
+        // requests are thereby served in the order they arrived.
+        let ready_at = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let rate = self.limit.requests as f64 / self.limit.per.as_secs_f64();
+            let refilled = now.duration_since(state.updated).as_secs_f64() * rate;
+            state.tokens = (state.tokens + refilled).min(self.limit.requests as f64) - 1.0;
+            state.updated = now;
+
+            if state.tokens >= 0.0 {
+                return;
+            }
+            now + Duration::from_secs_f64(-state.tokens / rate)
+        };
+
+        sleep_until(ready_at).await;
+    }
+}
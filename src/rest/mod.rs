@@ -1,24 +1,39 @@
 //! This module is used to interact with the REST API.
 
+mod batch;
+mod candles;
 mod error;
+mod limiter;
 mod model;
+mod order_builder;
+mod orderbook_view;
+mod rules;
 #[cfg(test)]
 pub(crate) mod tests;
 
 use boolinator::Boolinator;
+pub use candles::*;
 pub use error::*;
 pub use model::*;
+pub use order_builder::*;
+pub use orderbook_view::*;
+pub use rules::*;
 
-use crate::options::{Endpoint, Options};
-use chrono::{DateTime, Utc};
+use crate::options::{ConfigError, Endpoint, Options, RetryPolicy};
+use chrono::{DateTime, Duration, Utc};
+use futures::{stream, Stream, TryStreamExt};
 use hmac_sha256::HMAC;
+use limiter::RateLimiter;
 use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue},
     Client, ClientBuilder, Method,
 };
 use rust_decimal::prelude::*;
 use std::{
+    cmp::Reverse,
+    collections::HashMap,
     ops::Not,
+    sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
@@ -32,20 +47,42 @@ pub struct Rest {
     secret: Option<String>,
     client: Client,
     subaccount: Option<String>,
+    /// Headers sent with every request, i.e. the API key and subaccount
+    headers: HeaderMap,
     endpoint: Endpoint,
+    retry_policy: Option<RetryPolicy>,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+fn ceil_to_second(time: DateTime<Utc>) -> DateTime<Utc> {
+    match time.timestamp_subsec_nanos() {
+        0 => time,
+        nanos => time - Duration::nanoseconds(i64::from(nanos)) + Duration::seconds(1),
+    }
 }
 
 impl Rest {
-    // TODO: this should return Result<> if it can fail
-    pub fn new(
-        Options {
+    /// Panics if the options are invalid, see `Rest::try_new`.
+    pub fn new(options: Options) -> Self {
+        Self::try_new(options).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new(options: Options) -> Result<Self> {
+        Self::with_client(options, ClientBuilder::new().build()?)
+    }
+
+    /// Uses a preconfigured client, e.g. with a proxy, timeouts or custom
+    /// TLS roots, instead of creating a new one.
+    pub fn with_client(options: Options, client: Client) -> Result<Self> {
+        let Options {
             endpoint,
             key,
             secret,
             subaccount,
-        }: Options,
-    ) -> Self {
-        // Set default headers.
+            retry_policy,
+            rate_limits,
+        } = options;
+
         let headers = [
             (&key, endpoint.key_header()),
             (&subaccount, endpoint.subaccount_header()),
@@ -53,24 +90,31 @@ impl Rest {
         .iter()
         .flat_map(|(hdr_val, hdr_ident)| hdr_val.as_ref().map(|v| (v, hdr_ident)))
         .map(|(hdr_val, hdr_key)| {
-            (
-                HeaderName::from_str(hdr_key).unwrap(),
-                HeaderValue::from_str(hdr_val).unwrap(),
-            )
+            let invalid = || ConfigError::InvalidHeader(hdr_key.to_string());
+            Ok((
+                HeaderName::from_str(hdr_key).map_err(|_| invalid())?,
+                HeaderValue::from_str(hdr_val).map_err(|_| invalid())?,
+            ))
         })
-        .collect();
-
-        let client = ClientBuilder::new()
-            .default_headers(headers)
-            .build()
-            .unwrap();
+        .collect::<std::result::Result<_, ConfigError>>()?;
+        if let Some(rate_limits) = &rate_limits {
+            rate_limits.validate()?;
+        }
 
-        Self {
+        Ok(Self {
             secret,
             client,
             subaccount,
+            headers,
             endpoint,
-        }
+            retry_policy,
+            limiter: rate_limits.map(|limits| Arc::new(RateLimiter::new(&limits))),
+        })
+    }
+
+    /// The subaccount all requests are sent for, if any
+    pub fn subaccount(&self) -> Option<&str> {
+        self.subaccount.as_deref()
     }
 
     pub async fn request<R: Request>(&self, req: R) -> Result<R::Response> {
@@ -95,6 +139,112 @@ impl Rest {
         #[cfg(not(feature = "optimized-access"))]
         let url = format!("{}{}", self.endpoint.rest(), path);
 
+        let retry_safe = R::RETRY_SAFE || R::METHOD.is_idempotent();
+        let mut retries = 0;
+        loop {
+            match self.send::<R>(&url, &path, body.as_deref()).await {
+                Err(e) if retry_safe && e.is_transient() => match &self.retry_policy {
+                    Some(policy) if retries < policy.max_retries => {
+                        let backoff = policy.backoff(retries);
+                        log::debug!("retrying {} {} in {:?}: {}", R::METHOD, path, backoff, e);
+                        tokio::time::sleep(backoff).await;
+                        retries += 1;
+                    }
+                    _ => return Err(e),
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// Requests all items in the time window of a paginated request, walking
+    /// backwards from its end time (or now) to its start time one page at a
+    /// time. Items are returned newest first and without duplicates.
+    ///
+    /// ```no_run
+    /// # use ftx::{options::Options, rest::{GetFills, Rest}};
+    /// # use futures::TryStreamExt;
+    /// # async fn fills() -> ftx::rest::Result<()> {
+    /// let api = Rest::new(Options::from_env());
+    /// let fills: Vec<_> = api.paginate(GetFills::new("BTC-PERP")).try_collect().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn paginate<'a, R>(&'a self, req: R) -> impl Stream<Item = Result<R::Item>> + 'a
+    where
+        R: Paginated + 'a,
+        R::Item: 'a,
+    {
+        let start_time = req.start_time();
+        let limit = req.limit();
+        let state = (req, HashMap::new(), None, false);
+
+        stream::try_unfold(state, move |(mut req, mut seen, end, done)| async move {
+            if done {
+                return Ok::<_, Error>(None);
+            }
+
+            let page = self.request(req.clone()).await?;
+            let page_len = page.len();
+            let oldest = page.iter().filter_map(R::item_time).min();
+
+            let mut items: Vec<_> = page
+                .into_iter()
+                .filter(|item| seen.insert(R::item_key(item), R::item_time(item)).is_none())
+                .collect();
+            let found_new = !items.is_empty();
+            items.retain(|item| match (R::item_time(item), start_time) {
+                (Some(time), Some(start_time)) => time >= start_time,
+                _ => true,
+            });
+            items.sort_by_key(|item| Reverse(R::item_time(item)));
+
+            // The end time is sent in whole seconds, so round up to not skip
+            // items. Those seen already are filtered out. If more items than
+            // fit on a page share a second, the same page is returned again,
+            // so move back a second instead.
+            let next_end_time = match oldest {
+                Some(_) if !found_new => end.map(|end| end - Duration::seconds(1)),
+                Some(oldest) => Some(ceil_to_second(oldest)),
+                None => None,
+            };
+
+            // Only the items up to the next end time can be returned again
+            if let Some(next_end_time) = next_end_time {
+                seen.retain(|_, time| !matches!(time, Some(time) if *time > next_end_time));
+            }
+
+            // Stop once the API returns less than a full page, or the start
+            // time has been reached
+            let exhausted = page_len == 0 || matches!(limit, Some(limit) if page_len < limit);
+            let done = match (next_end_time, oldest) {
+                (Some(next_end_time), Some(oldest)) if !exhausted => {
+                    req.set_end_time(next_end_time);
+                    matches!(start_time, Some(start_time)
+                        if oldest <= start_time || next_end_time <= start_time)
+                }
+                _ => true,
+            };
+
+            Ok(Some((
+                stream::iter(items.into_iter().map(Ok)),
+                (req, seen, next_end_time, done),
+            )))
+        })
+        .try_flatten()
+    }
+
+    /// Signs and sends a request once
+    async fn send<R: Request>(
+        &self,
+        url: &str,
+        path: &str,
+        body: Option<&str>,
+    ) -> Result<R::Response> {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire(R::ORDER_PLACEMENT).await;
+        }
+
         let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
 
         log::trace!("timestamp: {}", timestamp);
@@ -102,7 +252,7 @@ impl Rest {
         log::trace!("path: {}", path);
         log::trace!("body: {:?}", body);
 
-        let headers: HeaderMap = IntoIterator::into_iter([
+        let mut headers: HeaderMap = IntoIterator::into_iter([
             // Always include content_type header
             Some((
                 reqwest::header::CONTENT_TYPE,
@@ -110,10 +260,10 @@ impl Rest {
             )),
             // Always include timestamp in header
             Some((
-                HeaderName::from_str(self.endpoint.timestamp_header())
-                    .map_err(|e| Error::Api(format!("invalid header {:?}", e)))?,
+                HeaderName::from_str(&self.endpoint.timestamp_header())
+                    .map_err(|e| Error::InvalidHeader(format!("{:?}", e)))?,
                 HeaderValue::from_str(&format!("{}", timestamp))
-                    .map_err(|e| Error::Api(format!("invalid header {:?}", e)))?,
+                    .map_err(|e| Error::InvalidHeader(format!("{:?}", e)))?,
             )),
             // If requires auth, include a sig
             R::AUTH.as_option().and_then(|_| {
@@ -124,48 +274,54 @@ impl Rest {
                     timestamp,
                     R::METHOD,
                     path,
-                    body.as_deref().unwrap_or("")
+                    body.unwrap_or("")
                 );
 
                 let sign = HMAC::mac(sign_payload.as_bytes(), secret.as_bytes());
                 let sign = hex::encode(sign);
                 Some((
-                    HeaderName::from_str(self.endpoint.sign_header()).ok()?,
+                    HeaderName::from_str(&self.endpoint.sign_header()).ok()?,
                     HeaderValue::from_str(&sign).ok()?,
                 ))
             }),
-            // If subaccount is set, include it
-            self.subaccount.as_ref().and_then(|subaccount| {
-                Some((
-                    HeaderName::from_str(self.endpoint.subaccount_header()).ok()?,
-                    HeaderValue::from_str(subaccount).ok()?,
-                ))
-            }),
         ])
         .flatten()
         .collect();
+        // Include the API key and subaccount, if set
+        headers.extend(self.headers.clone());
 
         let builder = self.client.request(R::METHOD, url).headers(headers);
         let builder = if let Some(body) = body {
-            builder.body(body)
+            builder.body(body.to_owned())
         } else {
             builder
         };
 
-        let resp_body = builder.send().await?.bytes().await?;
+        let resp = builder.send().await?;
+        let status = resp.status();
+        let resp_body = resp.bytes().await?;
 
         serde_json::from_reader(&*resp_body)
             .map(|res: SuccessResponse<R::Response>| res.result)
-            .map_err(|_| {
+            .map_err(|e| {
                 // try to parse the error response
-                serde_json::from_reader(&*resp_body)
-                    .map(|res: ErrorResponse| Error::Api(res.error))
-                    // otherwise return the raw response
-                    .unwrap_or_else(Into::into)
+                match serde_json::from_reader(&*resp_body) {
+                    Ok(ErrorResponse { error, .. }) => Error::from_response(status, error),
+                    // otherwise return the raw response of failed requests
+                    Err(_) if !status.is_success() => Error::from_response(
+                        status,
+                        String::from_utf8_lossy(&resp_body).into_owned(),
+                    ),
+                    Err(_) => e.into(),
+                }
             })
-            .map_err(Into::into)
     }
+}
 
+// The deprecated methods are kept as they were, without spelling out the
+// lifetimes of the requests in their return types
+#[allow(unknown_lints, mismatched_lifetime_syntaxes)]
+impl Rest {
     #[deprecated=deprecate_msg!()]
     pub async fn get_subaccounts(&self) -> Result<<GetSubaccounts as Request>::Response> {
         self.request(GetSubaccounts {}).await
@@ -428,7 +584,7 @@ impl Rest {
         retry_until_filled: Option<bool>,
         order_price: Option<Decimal>,
         trail_value: Option<Decimal>,
-    ) -> Result<OrderInfo> {
+    ) -> Result<TriggerOrderInfo> {
         self.request(PlaceTriggerOrder {
             market,
             side,
@@ -17,6 +17,8 @@ pub enum OrderType {
     TakeProfit,
 }
 
+// `#[default]` on variants requires a newer Rust than the supported 1.54
+#[allow(unknown_lints, clippy::derivable_impls)]
 impl Default for OrderType {
     fn default() -> OrderType {
         OrderType::Market
@@ -39,6 +41,7 @@ impl Default for OrderType {
 /// - Call the `get_order` REST API to see if the order status has been updated
 /// - Listen to orders over websockets to be notified of the update order status
 ///   as soon as it is available.
+///
 /// To get near-immediate feedback on the status of possibly-rejected orders,
 /// we recommend subscribing to the `Orders` channel over websockets.
 ///
@@ -62,6 +65,33 @@ pub enum OrderStatus {
     Closed,
 }
 
+/// The kind of trigger of a conditional order
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerType {
+    Stop,
+    TrailingStop,
+    TakeProfit,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TriggerOrderStatus {
+    /// Waiting for the trigger price to be reached
+    Open,
+    Cancelled,
+    /// The trigger price was reached and an order was sent
+    Triggered,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TwapOrderStatus {
+    Running,
+    Completed,
+    Cancelled,
+}
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub enum Side {
@@ -69,6 +99,8 @@ pub enum Side {
     Sell,
 }
 
+// `#[default]` on variants requires a newer Rust than the supported 1.54
+#[allow(unknown_lints, clippy::derivable_impls)]
 impl Default for Side {
     fn default() -> Side {
         Side::Buy
@@ -1,8 +1,9 @@
-use super::{common::Id, Request};
-use crate::ws::Fill;
+use super::common::{Coin, Id, Side, Symbol};
+use super::{Paginated, Request};
 use chrono::{DateTime, Utc};
 use http::Method;
-use serde::Serialize;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Default)]
 
@@ -42,3 +43,56 @@ impl Request for GetFills<'_> {
     const OPTIMIZED_ACCESS_SUPPORTED: bool = true;
     type Response = Vec<Fill>;
 }
+
+impl Paginated for GetFills<'_> {
+    type Item = Fill;
+    type Key = Id;
+
+    fn start_time(&self) -> Option<DateTime<Utc>> {
+        self.start_time
+    }
+
+    fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    fn set_end_time(&mut self, end_time: DateTime<Utc>) {
+        self.end_time = Some(end_time);
+    }
+
+    fn item_time(item: &Fill) -> Option<DateTime<Utc>> {
+        Some(item.time)
+    }
+
+    fn item_key(item: &Fill) -> Id {
+        item.id
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fill {
+    pub id: Id,
+    pub market: Option<Symbol>,
+    pub future: Option<Symbol>,
+    pub base_currency: Option<Coin>,
+    pub quote_currency: Option<Coin>,
+    pub r#type: String, // e.g. "order"
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub order_id: Option<Id>,
+    pub trade_id: Option<Id>,
+    pub time: DateTime<Utc>,
+    pub fee: Decimal,
+    pub fee_rate: Decimal,
+    pub fee_currency: Coin,
+    pub liquidity: Liquidity,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
@@ -1,5 +1,5 @@
 use super::common::Id;
-use super::Request;
+use super::{Paginated, Request};
 use chrono::{DateTime, Utc};
 use http::Method;
 use rust_decimal::Decimal;
@@ -39,3 +39,24 @@ impl Request for GetFundingPayments<'_> {
 
     type Response = FundingPayments;
 }
+
+impl Paginated for GetFundingPayments<'_> {
+    type Item = FundingPayment;
+    type Key = Id;
+
+    fn start_time(&self) -> Option<DateTime<Utc>> {
+        self.start_time
+    }
+
+    fn set_end_time(&mut self, end_time: DateTime<Utc>) {
+        self.end_time = Some(end_time);
+    }
+
+    fn item_time(item: &FundingPayment) -> Option<DateTime<Utc>> {
+        Some(item.time)
+    }
+
+    fn item_key(item: &FundingPayment) -> Id {
+        item.id
+    }
+}
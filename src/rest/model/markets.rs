@@ -1,5 +1,5 @@
 use super::common::{Coin, Id, MarketType, Resolution, Side, Symbol};
-use super::Request;
+use super::{Paginated, Request};
 use chrono::{DateTime, Utc};
 use http::Method;
 use rust_decimal::Decimal;
@@ -70,6 +70,8 @@ impl Request for GetMarket<'_> {
     }
 }
 
+/// A snapshot of an orderbook, with the levels of each side sorted best
+/// price first
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Orderbook {
@@ -179,6 +181,31 @@ impl Request for GetTrades<'_> {
     }
 }
 
+impl Paginated for GetTrades<'_> {
+    type Item = Trade;
+    type Key = Id;
+
+    fn start_time(&self) -> Option<DateTime<Utc>> {
+        self.start_time
+    }
+
+    fn limit(&self) -> Option<usize> {
+        self.limit.map(|limit| limit as usize)
+    }
+
+    fn set_end_time(&mut self, end_time: DateTime<Utc>) {
+        self.end_time = Some(end_time);
+    }
+
+    fn item_time(item: &Trade) -> Option<DateTime<Utc>> {
+        Some(item.time)
+    }
+
+    fn item_key(item: &Trade) -> Id {
+        item.id
+    }
+}
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Candle {
@@ -247,3 +274,28 @@ impl Request for GetHistoricalPrices<'_> {
         Cow::Owned(format!("/markets/{}/candles", self.market_name))
     }
 }
+
+impl Paginated for GetHistoricalPrices<'_> {
+    type Item = Candle;
+    type Key = DateTime<Utc>;
+
+    fn start_time(&self) -> Option<DateTime<Utc>> {
+        self.start_time
+    }
+
+    fn limit(&self) -> Option<usize> {
+        self.limit.map(|limit| limit as usize)
+    }
+
+    fn set_end_time(&mut self, end_time: DateTime<Utc>) {
+        self.end_time = Some(end_time);
+    }
+
+    fn item_time(item: &Candle) -> Option<DateTime<Utc>> {
+        Some(item.start_time)
+    }
+
+    fn item_key(item: &Candle) -> DateTime<Utc> {
+        item.start_time
+    }
+}
@@ -27,11 +27,19 @@ use http::Method;
 use serde::Serializer;
 use serde::{de::DeserializeOwned, ser::Error, Deserialize, Serialize};
 use std::borrow::Cow;
+use std::hash::Hash;
 
 pub trait Request: Serialize {
     const METHOD: Method;
     const PATH: &'static str;
     const AUTH: bool = false;
+    /// Whether the request may be sent again after a transient error even
+    /// though its method is not idempotent, e.g. because sending it twice
+    /// has the same effect as sending it once.
+    const RETRY_SAFE: bool = false;
+    /// Whether the request places, modifies or cancels orders, which counts
+    /// towards the order rate limit instead of the limit of other requests.
+    const ORDER_PLACEMENT: bool = false;
     #[cfg(feature = "optimized-access")]
     const OPTIMIZED_ACCESS_SUPPORTED: bool = false;
     type Response: DeserializeOwned;
@@ -41,6 +49,23 @@ pub trait Request: Serialize {
     }
 }
 
+/// A request for items within a time window, of which the API only returns a
+/// limited number at once, newest first. See `Rest::paginate`.
+pub trait Paginated: Request<Response = Vec<<Self as Paginated>::Item>> + Clone {
+    type Item;
+    /// Identifies an item, to remove duplicates at the boundaries of pages
+    type Key: Eq + Hash;
+
+    fn start_time(&self) -> Option<DateTime<Utc>>;
+    /// The maximum number of items of a page, if set
+    fn limit(&self) -> Option<usize> {
+        None
+    }
+    fn set_end_time(&mut self, end_time: DateTime<Utc>);
+    fn item_time(item: &Self::Item) -> Option<DateTime<Utc>>;
+    fn item_key(item: &Self::Item) -> Self::Key;
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct SuccessResponse<T> {
     pub success: bool,
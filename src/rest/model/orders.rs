@@ -1,5 +1,7 @@
-use super::common::{Id, OrderStatus, OrderType, Side};
-use super::Request;
+use super::common::{
+    Id, OrderStatus, OrderType, Side, TriggerOrderStatus, TriggerType, TwapOrderStatus,
+};
+use super::{Fill, Paginated, Request};
 use chrono::{DateTime, Utc};
 use http::Method;
 use rust_decimal::Decimal;
@@ -29,7 +31,7 @@ pub struct OrderInfo {
     pub retry_until_filled: Option<bool>,
     pub trigger_price: Option<Decimal>,
     pub order_price: Option<Decimal>,
-    pub triggered_at: Option<String>,
+    pub triggered_at: Option<DateTime<Utc>>,
     pub error: Option<String>,
 }
 
@@ -82,6 +84,7 @@ impl Request for PlaceOrder<'_> {
     const METHOD: Method = Method::POST;
     const PATH: &'static str = "/orders";
     const AUTH: bool = true;
+    const ORDER_PLACEMENT: bool = true;
     #[cfg(feature = "optimized-access")]
     const OPTIMIZED_ACCESS_SUPPORTED: bool = true;
     type Response = OrderInfo;
@@ -104,6 +107,7 @@ impl Request for ModifyOrder<'_> {
     const METHOD: Method = Method::POST;
     const PATH: &'static str = "/orders/{}/modify";
     const AUTH: bool = true;
+    const ORDER_PLACEMENT: bool = true;
 
     type Response = OrderInfo;
 
@@ -152,6 +156,7 @@ impl Request for CancelOrder {
     const METHOD: Method = Method::DELETE;
     const PATH: &'static str = "/orders/{}";
     const AUTH: bool = true;
+    const ORDER_PLACEMENT: bool = true;
     #[cfg(feature = "optimized-access")]
     const OPTIMIZED_ACCESS_SUPPORTED: bool = true;
     type Response = String;
@@ -187,6 +192,7 @@ impl Request for CancelAllOrder<'_> {
     const METHOD: Method = Method::DELETE;
     const PATH: &'static str = "/orders";
     const AUTH: bool = true;
+    const ORDER_PLACEMENT: bool = true;
 
     type Response = String;
 }
@@ -208,6 +214,7 @@ impl Request for CancelOrderByClientId<'_> {
     const METHOD: Method = Method::DELETE;
     const PATH: &'static str = "/orders/by_client_id/{}";
     const AUTH: bool = true;
+    const ORDER_PLACEMENT: bool = true;
     #[cfg(feature = "optimized-access")]
     const OPTIMIZED_ACCESS_SUPPORTED: bool = true;
     type Response = String;
@@ -270,6 +277,31 @@ impl Request for GetOrderHistory<'_> {
     type Response = Vec<OrderInfo>;
 }
 
+impl Paginated for GetOrderHistory<'_> {
+    type Item = OrderInfo;
+    type Key = Id;
+
+    fn start_time(&self) -> Option<DateTime<Utc>> {
+        self.start_time
+    }
+
+    fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    fn set_end_time(&mut self, end_time: DateTime<Utc>) {
+        self.end_time = Some(end_time);
+    }
+
+    fn item_time(item: &OrderInfo) -> Option<DateTime<Utc>> {
+        Some(item.created_at)
+    }
+
+    fn item_key(item: &OrderInfo) -> Id {
+        item.id
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct PlaceTriggerOrder<'a> {
@@ -277,6 +309,8 @@ pub struct PlaceTriggerOrder<'a> {
     pub side: Side,
     pub size: Decimal,
     pub r#type: OrderType,
+    // Trailing stops have no trigger price when placed
+    #[serde(skip_serializing_if = "Decimal::is_zero")]
     pub trigger_price: Decimal,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reduce_only: Option<bool>,
@@ -292,8 +326,227 @@ impl Request for PlaceTriggerOrder<'_> {
     const METHOD: Method = Method::POST;
     const PATH: &'static str = "/conditional_orders";
     const AUTH: bool = true;
+    const ORDER_PLACEMENT: bool = true;
 
-    type Response = OrderInfo;
+    type Response = TriggerOrderInfo;
+}
+
+/// A conditional order, which sends an order once its trigger price is
+/// reached
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerOrderInfo {
+    pub id: Id,
+    pub market: String,
+    pub future: Option<String>,
+    pub r#type: TriggerType,
+    /// The type of the order sent when triggered
+    pub order_type: OrderType,
+    pub side: Side,
+    pub size: Decimal,
+    pub status: TriggerOrderStatus,
+    pub trigger_price: Option<Decimal>,
+    /// The limit price of the order sent when triggered, `None` for market
+    /// orders
+    pub order_price: Option<Decimal>,
+    /// The distance of the trigger price to the best price since the
+    /// trailing stop was placed, negative for sell orders
+    pub trail_value: Option<Decimal>,
+    /// The price the trailing stop started trailing from
+    pub trail_start: Option<Decimal>,
+    pub reduce_only: Option<bool>,
+    pub retry_until_filled: Option<bool>,
+    pub filled_size: Option<Decimal>,
+    pub avg_fill_price: Option<Decimal>,
+    /// The ID of the order sent when triggered
+    pub order_id: Option<Id>,
+    pub created_at: DateTime<Utc>,
+    pub triggered_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+    pub cancel_reason: Option<String>,
+}
+
+/// An attempt of a conditional order to send an order
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trigger {
+    pub time: DateTime<Utc>,
+    pub order_size: Option<Decimal>,
+    pub filled_size: Option<Decimal>,
+    /// `None` if no order could be sent
+    pub order_id: Option<Id>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct GetTriggerOrders<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<TriggerType>,
+}
+
+impl<'a> GetTriggerOrders<'a> {
+    pub fn all_market() -> Self {
+        Self::default()
+    }
+
+    pub fn with_market(market: &'a str) -> Self {
+        Self {
+            market: Some(market),
+            ..Default::default()
+        }
+    }
+}
+
+impl Request for GetTriggerOrders<'_> {
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "/conditional_orders";
+    const AUTH: bool = true;
+
+    type Response = Vec<TriggerOrderInfo>;
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct GetTriggers {
+    #[serde(skip_serializing)]
+    pub id: Id,
+}
+
+impl GetTriggers {
+    pub fn new(order_id: Id) -> Self {
+        Self { id: order_id }
+    }
+}
+
+impl Request for GetTriggers {
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "/conditional_orders/{}/triggers";
+    const AUTH: bool = true;
+
+    type Response = Vec<Trigger>;
+
+    fn path(&self) -> Cow<'_, str> {
+        Cow::Owned(format!("/conditional_orders/{}/triggers", self.id))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTriggerOrderHistory<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub side: Option<Side>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<TriggerType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_type: Option<OrderType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "super::serialize_as_timestamp",
+        rename = "start_time"
+    )]
+    pub start_time: Option<DateTime<Utc>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "super::serialize_as_timestamp",
+        rename = "end_time"
+    )]
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+impl Request for GetTriggerOrderHistory<'_> {
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "/conditional_orders/history";
+    const AUTH: bool = true;
+
+    type Response = Vec<TriggerOrderInfo>;
+}
+
+impl Paginated for GetTriggerOrderHistory<'_> {
+    type Item = TriggerOrderInfo;
+    type Key = Id;
+
+    fn start_time(&self) -> Option<DateTime<Utc>> {
+        self.start_time
+    }
+
+    fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    fn set_end_time(&mut self, end_time: DateTime<Utc>) {
+        self.end_time = Some(end_time);
+    }
+
+    fn item_time(item: &TriggerOrderInfo) -> Option<DateTime<Utc>> {
+        Some(item.created_at)
+    }
+
+    fn item_key(item: &TriggerOrderInfo) -> Id {
+        item.id
+    }
+}
+
+/// Modifies a conditional order. FTX replaces it with an order of a new ID,
+/// which is returned.
+#[derive(Debug, Clone, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifyTriggerOrder {
+    #[serde(skip_serializing)]
+    pub id: Id,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<Decimal>,
+    /// Only for stop and take profit orders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_price: Option<Decimal>,
+    /// Only for stop limit and take profit limit orders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_price: Option<Decimal>,
+    /// Only for trailing stop orders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trail_value: Option<Decimal>,
+}
+
+impl Request for ModifyTriggerOrder {
+    const METHOD: Method = Method::POST;
+    const PATH: &'static str = "/conditional_orders/{}/modify";
+    const AUTH: bool = true;
+    const ORDER_PLACEMENT: bool = true;
+
+    type Response = TriggerOrderInfo;
+
+    fn path(&self) -> Cow<'_, str> {
+        Cow::Owned(format!("/conditional_orders/{}/modify", self.id))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct CancelTriggerOrder {
+    #[serde(skip_serializing)]
+    pub id: Id,
+}
+
+impl CancelTriggerOrder {
+    pub fn new(order_id: Id) -> Self {
+        Self { id: order_id }
+    }
+}
+
+impl Request for CancelTriggerOrder {
+    const METHOD: Method = Method::DELETE;
+    const PATH: &'static str = "/conditional_orders/{}";
+    const AUTH: bool = true;
+    const ORDER_PLACEMENT: bool = true;
+
+    type Response = String;
+
+    fn path(&self) -> Cow<'_, str> {
+        Cow::Owned(format!("/conditional_orders/{}", self.id))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Default)]
@@ -311,6 +564,7 @@ impl Request for ModifyOrderByClientId<'_> {
     const METHOD: Method = Method::POST;
     const PATH: &'static str = "/orders/by_client_id/{}/modify";
     const AUTH: bool = true;
+    const ORDER_PLACEMENT: bool = true;
 
     type Response = OrderInfo;
 
@@ -318,3 +572,134 @@ impl Request for ModifyOrderByClientId<'_> {
         Cow::Owned(format!("/orders/by_client_id/{}/modify", self.client_id))
     }
 }
+
+/// An order that FTX executes in many small orders over a period of time
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwapOrderInfo {
+    pub id: Id,
+    pub market: String,
+    pub side: Side,
+    pub size: Decimal,
+    pub status: TwapOrderStatus,
+    pub filled_size: Option<Decimal>,
+    pub avg_fill_price: Option<Decimal>,
+    pub duration_seconds: u64,
+    pub randomize_size: Option<bool>,
+    pub max_spread: Option<Decimal>,
+    pub max_distance: Option<Decimal>,
+    pub max_individual_order_size: Option<Decimal>,
+    pub reduce_only: Option<bool>,
+    pub created_at: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub cancel_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceTwapOrder<'a> {
+    pub market: &'a str,
+    pub side: Side,
+    pub size: Decimal,
+    /// How long the execution takes
+    pub duration_seconds: u64,
+    /// Varies the sizes of the individual orders
+    pub randomize_size: bool,
+    /// Pauses the execution while the spread exceeds this fraction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_spread: Option<Decimal>,
+    /// Pauses the execution while the price is further than this fraction
+    /// from the price when the order was placed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_distance: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_individual_order_size: Option<Decimal>,
+    pub reduce_only: bool,
+}
+
+impl Request for PlaceTwapOrder<'_> {
+    const METHOD: Method = Method::POST;
+    const PATH: &'static str = "/twap_orders";
+    const AUTH: bool = true;
+    const ORDER_PLACEMENT: bool = true;
+
+    type Response = TwapOrderInfo;
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct GetTwapOrders<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<TwapOrderStatus>,
+}
+
+impl<'a> GetTwapOrders<'a> {
+    pub fn all_market() -> Self {
+        Self::default()
+    }
+
+    pub fn with_market(market: &'a str) -> Self {
+        Self {
+            market: Some(market),
+            ..Default::default()
+        }
+    }
+}
+
+impl Request for GetTwapOrders<'_> {
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "/twap_orders";
+    const AUTH: bool = true;
+
+    type Response = Vec<TwapOrderInfo>;
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct CancelTwapOrder {
+    #[serde(skip_serializing)]
+    pub id: Id,
+}
+
+impl CancelTwapOrder {
+    pub fn new(order_id: Id) -> Self {
+        Self { id: order_id }
+    }
+}
+
+impl Request for CancelTwapOrder {
+    const METHOD: Method = Method::DELETE;
+    const PATH: &'static str = "/twap_orders/{}";
+    const AUTH: bool = true;
+    const ORDER_PLACEMENT: bool = true;
+
+    type Response = String;
+
+    fn path(&self) -> Cow<'_, str> {
+        Cow::Owned(format!("/twap_orders/{}", self.id))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct GetTwapOrderFills {
+    #[serde(skip_serializing)]
+    pub id: Id,
+}
+
+impl GetTwapOrderFills {
+    pub fn new(order_id: Id) -> Self {
+        Self { id: order_id }
+    }
+}
+
+impl Request for GetTwapOrderFills {
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "/twap_orders/{}/fills";
+    const AUTH: bool = true;
+
+    type Response = Vec<Fill>;
+
+    fn path(&self) -> Cow<'_, str> {
+        Cow::Owned(format!("/twap_orders/{}/fills", self.id))
+    }
+}
@@ -1,5 +1,5 @@
 use super::common::{Coin, DepositStatus, Id, WithdrawStatus};
-use super::Request;
+use super::{Paginated, Request};
 use chrono::{DateTime, Utc};
 use http::Method;
 use rust_decimal::prelude::*;
@@ -64,6 +64,37 @@ impl Request for GetWalletDeposits {
     type Response = Vec<WalletDeposit>;
 }
 
+fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+impl Paginated for GetWalletDeposits {
+    type Item = WalletDeposit;
+    type Key = Id;
+
+    fn start_time(&self) -> Option<DateTime<Utc>> {
+        self.start_time
+    }
+
+    fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    fn set_end_time(&mut self, end_time: DateTime<Utc>) {
+        self.end_time = Some(end_time);
+    }
+
+    fn item_time(item: &WalletDeposit) -> Option<DateTime<Utc>> {
+        parse_time(&item.time)
+    }
+
+    fn item_key(item: &WalletDeposit) -> Id {
+        item.id
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct GetWalletBalances {}
@@ -193,6 +224,36 @@ impl Request for GetWalletWithdrawals {
     type Response = Vec<WalletWithdrawal>;
 }
 
+impl Paginated for GetWalletWithdrawals {
+    type Item = WalletWithdrawal;
+    type Key = (String, String, Decimal, Option<String>);
+
+    fn start_time(&self) -> Option<DateTime<Utc>> {
+        self.start_time
+    }
+
+    fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    fn set_end_time(&mut self, end_time: DateTime<Utc>) {
+        self.end_time = Some(end_time);
+    }
+
+    fn item_time(item: &WalletWithdrawal) -> Option<DateTime<Utc>> {
+        parse_time(&item.time)
+    }
+
+    fn item_key(item: &WalletWithdrawal) -> (String, String, Decimal, Option<String>) {
+        (
+            item.time.clone(),
+            item.coin.clone(),
+            item.size,
+            item.txid.clone(),
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct RequestWithdrawal {
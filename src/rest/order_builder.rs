@@ -0,0 +1,301 @@
+use super::{OrderType, PlaceOrder, PlaceTriggerOrder, Side};
+use rust_decimal::Decimal;
+use std::marker::PhantomData;
+
+/// Builds order requests of which only valid combinations can be expressed.
+///
+/// Limit orders require a price and market orders have none, a limit order
+/// can be either post-only or immediate-or-cancel but not both, and trigger
+/// orders only offer the options of their kind. Contradictory orders do not
+/// compile:
+///
+/// ```compile_fail
+/// # use ftx::rest::{Order, Side};
+/// # use rust_decimal_macros::dec;
+/// let order = Order::limit("BTC-PERP", Side::Buy, dec!(20000), dec!(0.1))
+///     .post_only()
+///     .ioc();
+/// ```
+///
+/// ```no_run
+/// # use ftx::{options::Options, rest::{Order, Rest, Side}};
+/// # use rust_decimal_macros::dec;
+/// # async fn example() -> ftx::rest::Result<()> {
+/// let api = Rest::new(Options::from_env());
+/// let order = Order::limit("BTC-PERP", Side::Buy, dec!(20000), dec!(0.1))
+///     .post_only()
+///     .client_id("entry");
+/// api.request(order.build()).await?;
+///
+/// let stop = Order::stop("BTC-PERP", Side::Sell, dec!(19000), dec!(0.1))
+///     .limit(dec!(18900))
+///     .reduce_only();
+/// api.request(stop.build()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct Order;
+
+impl Order {
+    pub fn limit(market: &str, side: Side, price: Decimal, size: Decimal) -> LimitOrder<'_> {
+        LimitOrder {
+            request: PlaceOrder {
+                market,
+                side,
+                price: Some(price),
+                r#type: OrderType::Limit,
+                size,
+                ..Default::default()
+            },
+            time_in_force: PhantomData,
+        }
+    }
+
+    pub fn market(market: &str, side: Side, size: Decimal) -> MarketOrder<'_> {
+        MarketOrder {
+            request: PlaceOrder {
+                market,
+                side,
+                price: None,
+                r#type: OrderType::Market,
+                size,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// A stop loss, which sends a market order once the price reaches
+    /// `trigger_price`, or a limit order with `StopOrder::limit`.
+    pub fn stop(market: &str, side: Side, trigger_price: Decimal, size: Decimal) -> StopOrder<'_> {
+        StopOrder {
+            request: trigger_order(market, side, OrderType::Stop, trigger_price, size),
+        }
+    }
+
+    /// Like `Order::stop`, but for closing a position in profit.
+    pub fn take_profit(
+        market: &str,
+        side: Side,
+        trigger_price: Decimal,
+        size: Decimal,
+    ) -> StopOrder<'_> {
+        StopOrder {
+            request: trigger_order(market, side, OrderType::TakeProfit, trigger_price, size),
+        }
+    }
+
+    /// A stop whose trigger price follows the best price at a distance of
+    /// `trail_value`, which is negative for sell orders. Sends a market
+    /// order when triggered.
+    pub fn trailing_stop(
+        market: &str,
+        side: Side,
+        trail_value: Decimal,
+        size: Decimal,
+    ) -> TrailingStopOrder<'_> {
+        TrailingStopOrder {
+            request: PlaceTriggerOrder {
+                trail_value: Some(trail_value),
+                ..trigger_order(market, side, OrderType::TrailingStop, Decimal::ZERO, size)
+            },
+        }
+    }
+}
+
+fn trigger_order(
+    market: &str,
+    side: Side,
+    r#type: OrderType,
+    trigger_price: Decimal,
+    size: Decimal,
+) -> PlaceTriggerOrder<'_> {
+    PlaceTriggerOrder {
+        market,
+        side,
+        size,
+        r#type,
+        trigger_price,
+        ..Default::default()
+    }
+}
+
+/// Time in force of a limit order that rests on the book until it is
+/// filled or cancelled
+#[derive(Copy, Clone, Debug)]
+pub struct GoodTilCancelled;
+
+/// Time in force of a limit order that is only placed if it does not match
+/// immediately
+#[derive(Copy, Clone, Debug)]
+pub struct PostOnly;
+
+/// Time in force of a limit order that is cancelled as far as it does not
+/// match immediately
+#[derive(Copy, Clone, Debug)]
+pub struct ImmediateOrCancel;
+
+/// A limit order built with `Order::limit`
+#[derive(Clone, Debug)]
+pub struct LimitOrder<'a, T = GoodTilCancelled> {
+    request: PlaceOrder<'a>,
+    time_in_force: PhantomData<T>,
+}
+
+impl<'a> LimitOrder<'a, GoodTilCancelled> {
+    #[must_use]
+    pub fn post_only(self) -> LimitOrder<'a, PostOnly> {
+        LimitOrder {
+            request: PlaceOrder {
+                post_only: true,
+                ..self.request
+            },
+            time_in_force: PhantomData,
+        }
+    }
+
+    #[must_use]
+    pub fn ioc(self) -> LimitOrder<'a, ImmediateOrCancel> {
+        LimitOrder {
+            request: PlaceOrder {
+                ioc: true,
+                ..self.request
+            },
+            time_in_force: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> LimitOrder<'a, T> {
+    #[must_use]
+    pub fn reduce_only(mut self) -> Self {
+        self.request.reduce_only = true;
+        self
+    }
+
+    #[must_use]
+    pub fn client_id(mut self, client_id: &'a str) -> Self {
+        self.request.client_id = Some(client_id);
+        self
+    }
+
+    /// Rejects the order instead of adjusting its price when it is outside
+    /// of the price band of the market
+    #[must_use]
+    pub fn reject_on_price_band(mut self) -> Self {
+        self.request.reject_on_price_band = true;
+        self
+    }
+
+    pub fn build(self) -> PlaceOrder<'a> {
+        self.request
+    }
+}
+
+impl<'a, T> From<LimitOrder<'a, T>> for PlaceOrder<'a> {
+    fn from(order: LimitOrder<'a, T>) -> Self {
+        order.build()
+    }
+}
+
+/// A market order built with `Order::market`
+#[derive(Clone, Debug)]
+pub struct MarketOrder<'a> {
+    request: PlaceOrder<'a>,
+}
+
+impl<'a> MarketOrder<'a> {
+    #[must_use]
+    pub fn reduce_only(mut self) -> Self {
+        self.request.reduce_only = true;
+        self
+    }
+
+    #[must_use]
+    pub fn client_id(mut self, client_id: &'a str) -> Self {
+        self.request.client_id = Some(client_id);
+        self
+    }
+
+    pub fn build(self) -> PlaceOrder<'a> {
+        self.request
+    }
+}
+
+impl<'a> From<MarketOrder<'a>> for PlaceOrder<'a> {
+    fn from(order: MarketOrder<'a>) -> Self {
+        order.build()
+    }
+}
+
+/// A stop or take profit order built with `Order::stop` or
+/// `Order::take_profit`
+#[derive(Clone, Debug)]
+pub struct StopOrder<'a> {
+    request: PlaceTriggerOrder<'a>,
+}
+
+impl<'a> StopOrder<'a> {
+    /// Sends a limit order at `order_price` instead of a market order when
+    /// triggered
+    #[must_use]
+    pub fn limit(mut self, order_price: Decimal) -> Self {
+        self.request.order_price = Some(order_price);
+        self
+    }
+
+    #[must_use]
+    pub fn reduce_only(mut self) -> Self {
+        self.request.reduce_only = Some(true);
+        self
+    }
+
+    /// Whether to keep sending orders until the size is filled, which FTX
+    /// does by default for market orders
+    #[must_use]
+    pub fn retry_until_filled(mut self, retry: bool) -> Self {
+        self.request.retry_until_filled = Some(retry);
+        self
+    }
+
+    pub fn build(self) -> PlaceTriggerOrder<'a> {
+        self.request
+    }
+}
+
+impl<'a> From<StopOrder<'a>> for PlaceTriggerOrder<'a> {
+    fn from(order: StopOrder<'a>) -> Self {
+        order.build()
+    }
+}
+
+/// A trailing stop built with `Order::trailing_stop`
+#[derive(Clone, Debug)]
+pub struct TrailingStopOrder<'a> {
+    request: PlaceTriggerOrder<'a>,
+}
+
+impl<'a> TrailingStopOrder<'a> {
+    #[must_use]
+    pub fn reduce_only(mut self) -> Self {
+        self.request.reduce_only = Some(true);
+        self
+    }
+
+    /// Whether to keep sending orders until the size is filled
+    #[must_use]
+    pub fn retry_until_filled(mut self, retry: bool) -> Self {
+        self.request.retry_until_filled = Some(retry);
+        self
+    }
+
+    pub fn build(self) -> PlaceTriggerOrder<'a> {
+        self.request
+    }
+}
+
+impl<'a> From<TrailingStopOrder<'a>> for PlaceTriggerOrder<'a> {
+    fn from(order: TrailingStopOrder<'a>) -> Self {
+        order.build()
+    }
+}
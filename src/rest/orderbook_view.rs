@@ -0,0 +1,87 @@
+use super::{Orderbook, Side};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+/// Read-only access to the levels of an orderbook.
+///
+/// Implemented by the snapshots of `GetOrderBook` as well as the orderbooks
+/// maintained from websocket updates, so that code which inspects orderbooks
+/// works with either.
+pub trait OrderbookView {
+    /// Returns the bids as (price, quantity), best price first
+    fn bid_levels(&self) -> Box<dyn Iterator<Item = (Decimal, Decimal)> + '_>;
+
+    /// Returns the asks as (price, quantity), best price first
+    fn ask_levels(&self) -> Box<dyn Iterator<Item = (Decimal, Decimal)> + '_>;
+
+    /// Returns the price and quantity of the best bid
+    fn top_bid(&self) -> Option<(Decimal, Decimal)> {
+        self.bid_levels().next()
+    }
+
+    /// Returns the price and quantity of the best ask
+    fn top_ask(&self) -> Option<(Decimal, Decimal)> {
+        self.ask_levels().next()
+    }
+
+    /// Returns the midpoint between the best bid price and best ask price.
+    fn mid_price(&self) -> Option<Decimal> {
+        let (bid, _) = self.top_bid()?;
+        let (ask, _) = self.top_ask()?;
+        Some((bid + ask) / dec!(2))
+    }
+
+    /// Returns the cumulative quantity of bids and asks priced within `bps`
+    /// basis points of the mid price.
+    /// (bid_quantity, ask_quantity)
+    fn depth(&self, bps: Decimal) -> Option<(Decimal, Decimal)> {
+        let mid = self.mid_price()?;
+        let distance = mid * bps / dec!(10000);
+
+        let bids = self
+            .bid_levels()
+            .take_while(|(price, _)| *price >= mid - distance)
+            .map(|(_, size)| size)
+            .sum();
+        let asks = self
+            .ask_levels()
+            .take_while(|(price, _)| *price <= mid + distance)
+            .map(|(_, size)| size)
+            .sum();
+        Some((bids, asks))
+    }
+
+    /// Returns the expected execution price of a market order of the given
+    /// side and quantity. Returns None if the quantity is not positive or
+    /// exceeds the liquidity available on that side of the orderbook.
+    fn quote(&self, side: Side, quantity: Decimal) -> Option<Decimal> {
+        if quantity <= Decimal::ZERO {
+            return None;
+        }
+
+        let mut levels = match side {
+            Side::Buy => self.ask_levels(),
+            Side::Sell => self.bid_levels(),
+        };
+        let mut remaining = quantity;
+        let mut notional = Decimal::ZERO;
+        while remaining > Decimal::ZERO {
+            let (price, size) = levels.next()?;
+            let matched = size.min(remaining);
+            notional += price * matched;
+            remaining -= matched;
+        }
+
+        Some(notional / quantity)
+    }
+}
+
+impl OrderbookView for Orderbook {
+    fn bid_levels(&self) -> Box<dyn Iterator<Item = (Decimal, Decimal)> + '_> {
+        Box::new(self.bids.iter().copied())
+    }
+
+    fn ask_levels(&self) -> Box<dyn Iterator<Item = (Decimal, Decimal)> + '_> {
+        Box::new(self.asks.iter().copied())
+    }
+}
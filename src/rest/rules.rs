@@ -0,0 +1,150 @@
+use super::{
+    Error, Future, GetFutures, GetMarkets, Market, OrderType, PlaceOrder, Rest, Result, Symbol,
+};
+use rust_decimal::prelude::*;
+use std::collections::HashMap;
+
+/// The price and size constraints of a single market.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MarketRule {
+    pub price_increment: Decimal,
+    pub size_increment: Decimal,
+    /// The smallest size an order may have
+    pub min_size: Decimal,
+}
+
+impl From<&Market> for MarketRule {
+    fn from(market: &Market) -> Self {
+        Self {
+            price_increment: market.price_increment,
+            size_increment: market.size_increment,
+            min_size: market.min_provide_size,
+        }
+    }
+}
+
+impl From<&Future> for MarketRule {
+    fn from(future: &Future) -> Self {
+        Self {
+            price_increment: future.price_increment,
+            size_increment: future.size_increment,
+            // Futures do not report a minimum size separately
+            min_size: future.size_increment,
+        }
+    }
+}
+
+/// A cache of the price and size constraints of markets, which allows
+/// checking orders before they are sent.
+///
+/// The constraints of a market rarely change, but they do occasionally, so
+/// long running processes should `fetch` them again from time to time.
+#[derive(Clone, Debug, Default)]
+pub struct MarketRules {
+    rules: HashMap<Symbol, MarketRule>,
+}
+
+impl MarketRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetches the constraints of all markets and futures.
+    pub async fn fetch(rest: &Rest) -> Result<Self> {
+        let mut rules = Self::new();
+        for market in rest.request(GetMarkets {}).await? {
+            rules.insert_market(&market);
+        }
+        for future in rest.request(GetFutures {}).await? {
+            // Markets are more detailed, since they include the minimum size
+            if rules.get(&future.name).is_none() {
+                rules.insert_future(&future);
+            }
+        }
+
+        Ok(rules)
+    }
+
+    pub fn insert_market(&mut self, market: &Market) {
+        self.rules.insert(market.name.clone(), market.into());
+    }
+
+    pub fn insert_future(&mut self, future: &Future) {
+        self.rules.insert(future.name.clone(), future.into());
+    }
+
+    pub fn get(&self, market: &str) -> Option<&MarketRule> {
+        self.rules.get(market)
+    }
+
+    fn rule(&self, market: &str) -> Result<&MarketRule> {
+        self.get(market)
+            .ok_or_else(|| Error::UnknownMarket(market.to_owned()))
+    }
+
+    /// Rounds a price to the nearest multiple of the price increment of the
+    /// market.
+    pub fn round_price(&self, market: &str, price: Decimal) -> Result<Decimal> {
+        let increment = self.rule(market)?.price_increment;
+        Ok(round_to_increment(
+            price,
+            increment,
+            RoundingStrategy::MidpointAwayFromZero,
+        ))
+    }
+
+    /// Rounds a size down to a multiple of the size increment of the market,
+    /// so that an order never ends up larger than intended.
+    pub fn round_size(&self, market: &str, size: Decimal) -> Result<Decimal> {
+        let increment = self.rule(market)?.size_increment;
+        Ok(round_to_increment(
+            size,
+            increment,
+            RoundingStrategy::ToZero,
+        ))
+    }
+
+    /// Checks an order against the constraints of its market, which would
+    /// otherwise only be enforced by the API.
+    pub fn validate(&self, order: &PlaceOrder<'_>) -> Result<()> {
+        let rule = self.rule(order.market)?;
+
+        match (order.r#type, order.price) {
+            (OrderType::Limit, None) => return Err(Error::PlacingLimitOrderRequiresPrice),
+            (_, Some(price)) if !is_multiple(price, rule.price_increment) => {
+                return Err(Error::InvalidPriceIncrement {
+                    price,
+                    increment: rule.price_increment,
+                })
+            }
+            _ => {}
+        }
+
+        if !is_multiple(order.size, rule.size_increment) {
+            return Err(Error::InvalidSizeIncrement {
+                size: order.size,
+                increment: rule.size_increment,
+            });
+        }
+        if order.size < rule.min_size {
+            return Err(Error::SizeBelowMinimum {
+                size: order.size,
+                min_size: rule.min_size,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+fn round_to_increment(value: Decimal, increment: Decimal, strategy: RoundingStrategy) -> Decimal {
+    if increment.is_zero() {
+        return value;
+    }
+    let rounded = (value / increment).round_dp_with_strategy(0, strategy) * increment;
+    rounded.round_dp(increment.scale()).normalize()
+}
+
+fn is_multiple(value: Decimal, increment: Decimal) -> bool {
+    increment.is_zero() || (value % increment).is_zero()
+}
@@ -1,7 +1,21 @@
 use super::*;
+// The mock exchange requires websockets
+#[cfg(feature = "ws")]
+use crate::mock::{market, trade, MockExchange, MockResponse, OrderBehavior};
+#[cfg(feature = "ws")]
+use crate::options::RetryPolicy;
+use crate::options::{ConfigError, RateLimit, RateLimits};
+use chrono::TimeZone;
 use dotenvy::dotenv;
+#[cfg(feature = "ws")]
+use futures::TryStreamExt;
+#[cfg(feature = "ws")]
+use reqwest::StatusCode;
 use rust_decimal_macros::dec;
 use std::env::var;
+use std::time::Duration;
+#[cfg(feature = "ws")]
+use std::time::Instant;
 
 async fn init_api() -> Rest {
     dotenv().ok();
@@ -26,7 +40,7 @@ fn read_only<T: std::fmt::Debug>(result: Result<T>) {
     result.expect_err("Expected error");
     //TODO: match specific error
     // match result {
-    //     Err(Error::Api(error)) if error == *"Not allowed with read-only permissions" => {}
+    //     Err(Error::Api(_, error)) if error == *"Not allowed with read-only permissions" => {}
     //     _ => panic!("Expected read-only subaccount."),
     // }
 }
@@ -426,3 +440,1034 @@ async fn get_open_orders() {
         .await
         .unwrap();
 }
+
+#[cfg(feature = "ws")]
+async fn init_mock_api() -> (MockExchange, Rest) {
+    let mock = MockExchange::start().await.unwrap();
+    let api = Rest::new(
+        mock.options()
+            .authenticate("key".to_owned(), "secret".to_owned()),
+    );
+    (mock, api)
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_markets() {
+    let (mock, api) = init_mock_api().await;
+    mock.add_market(market("BTC/USD", dec!(1), dec!(0.0001)));
+    mock.add_market(market("BTC-PERP", dec!(1), dec!(0.0001)));
+    mock.set_orderbook(
+        "BTC/USD",
+        &[(dec!(99), dec!(1)), (dec!(100), dec!(2))],
+        &[(dec!(101), dec!(3)), (dec!(102), dec!(4))],
+    );
+
+    assert_eq!(api.request(GetMarkets {}).await.unwrap().len(), 2);
+
+    let market = api.request(GetMarket::new("BTC/USD")).await.unwrap();
+    assert_eq!(market.market_type, MarketType::Spot);
+    assert_eq!(market.base_currency.as_deref(), Some("BTC"));
+
+    let orderbook = api
+        .request(GetOrderBook::with_depth("BTC/USD", 1))
+        .await
+        .unwrap();
+    assert_eq!(orderbook.bids, vec![(dec!(100), dec!(2))]);
+    assert_eq!(orderbook.asks, vec![(dec!(101), dec!(3))]);
+
+    match api.request(GetMarket::new("ETH/USD")).await {
+        Err(Error::Api(status, error)) => {
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(error, "No such market: ETH/USD");
+        }
+        other => panic!("Expected API error, got {:?}", other),
+    }
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_authentication() {
+    let (mock, api) = init_mock_api().await;
+    mock.set_credentials("key", "secret");
+
+    api.request(GetPositions {}).await.unwrap();
+
+    let request = mock.requests().pop().unwrap();
+    assert_eq!(request.path, "/positions");
+    assert_eq!(request.headers["FTX-KEY"], "key");
+
+    match Rest::new(mock.options()).request(GetPositions {}).await {
+        Err(Error::AuthFailed(StatusCode::UNAUTHORIZED, _)) => {}
+        other => panic!("Expected authentication error, got {:?}", other),
+    }
+
+    match Rest::new(
+        mock.options()
+            .authenticate("key".to_owned(), "wrong".to_owned()),
+    )
+    .request(GetPositions {})
+    .await
+    {
+        Err(Error::AuthFailed(_, error)) => assert_eq!(error, "Not logged in: Invalid signature"),
+        other => panic!("Expected authentication error, got {:?}", other),
+    }
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_place_modify_cancel_orders() {
+    let (mock, api) = init_mock_api().await;
+
+    let initial_order = api
+        .request(PlaceOrder {
+            market: "ETH-PERP",
+            side: Side::Buy,
+            price: Some(dec!(1000)),
+            r#type: OrderType::Limit,
+            size: dec!(0.001),
+            post_only: true,
+            client_id: Some("initial"),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(OrderStatus::New, initial_order.status);
+    assert_eq!(
+        OrderStatus::Open,
+        api.request(GetOrderByClientId::new("initial"))
+            .await
+            .unwrap()
+            .status
+    );
+
+    let modified_order = api
+        .request(ModifyOrder {
+            id: initial_order.id,
+            price: Some(dec!(990)),
+            size: Some(dec!(0.002)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_ne!(initial_order.id, modified_order.id);
+    assert_eq!(Some(dec!(990)), modified_order.price);
+    assert_eq!(dec!(0.002), modified_order.size);
+    assert_eq!(Some("initial"), modified_order.client_id.as_deref());
+
+    let open_orders = api.request(GetOpenOrders::all_market()).await.unwrap();
+    assert_eq!(1, open_orders.len());
+    assert_eq!(modified_order.id, open_orders[0].id);
+
+    assert_eq!(
+        "Order queued for cancellation",
+        api.request(CancelOrder::new(modified_order.id))
+            .await
+            .unwrap()
+    );
+    let cancelled_order = api.request(GetOrder::new(modified_order.id)).await.unwrap();
+    assert_eq!(OrderStatus::Closed, cancelled_order.status);
+    assert!(matches!(
+        api.request(CancelOrder::new(modified_order.id)).await,
+        Err(Error::OrderAlreadyClosed(StatusCode::BAD_REQUEST, _))
+    ));
+    assert!(matches!(
+        api.request(GetOrder::new(12345)).await,
+        Err(Error::OrderNotFound(StatusCode::NOT_FOUND, _))
+    ));
+
+    // Filled orders produce fills
+    mock.push_order_behavior(OrderBehavior::Fill);
+    let filled_order = api
+        .request(PlaceOrder {
+            market: "ETH-PERP",
+            side: Side::Sell,
+            price: Some(dec!(1010)),
+            r#type: OrderType::Limit,
+            size: dec!(0.003),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let fills = api.request(GetFills::new("ETH-PERP")).await.unwrap();
+    assert_eq!(1, fills.len());
+    assert_eq!(Some(filled_order.id), fills[0].order_id);
+    assert_eq!(dec!(1010), fills[0].price);
+    assert!(api
+        .request(GetOpenOrders::all_market())
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_retry_policy() {
+    let mock = MockExchange::start().await.unwrap();
+    let api = Rest::new(mock.options().with_retry_policy(RetryPolicy {
+        max_retries: 2,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+    }));
+    let rate_limited = || MockResponse::error(StatusCode::TOO_MANY_REQUESTS, "Please slow down");
+
+    // Idempotent requests are retried
+    mock.respond_once(Method::GET, "/markets", rate_limited());
+    mock.respond_once(
+        Method::GET,
+        "/markets",
+        MockResponse::error(StatusCode::BAD_GATEWAY, "<html>Bad Gateway</html>"),
+    );
+    api.request(GetMarkets {}).await.unwrap();
+    assert_eq!(3, mock.requests().len());
+
+    // Until the retries are exhausted
+    for _ in 0..3 {
+        mock.respond_once(Method::GET, "/markets", rate_limited());
+    }
+    match api.request(GetMarkets {}).await {
+        Err(Error::RateLimited(StatusCode::TOO_MANY_REQUESTS, error)) => {
+            assert_eq!(error, "Please slow down")
+        }
+        other => panic!("Expected rate limit error, got {:?}", other),
+    }
+    assert_eq!(6, mock.requests().len());
+
+    // Permanent errors are not retried
+    mock.respond_once(
+        Method::GET,
+        "/markets",
+        MockResponse::error(StatusCode::BAD_REQUEST, "Invalid parameter"),
+    );
+    match api.request(GetMarkets {}).await {
+        Err(Error::Api(StatusCode::BAD_REQUEST, _)) => {}
+        other => panic!("Expected API error, got {:?}", other),
+    }
+    assert_eq!(7, mock.requests().len());
+
+    // Neither are requests that are not idempotent
+    mock.respond_once(Method::POST, "/orders", rate_limited());
+    let order = PlaceOrder {
+        market: "BTC-PERP",
+        side: Side::Buy,
+        price: Some(dec!(20000)),
+        r#type: OrderType::Limit,
+        size: dec!(0.001),
+        ..Default::default()
+    };
+    let api = Rest::new(
+        mock.options()
+            .authenticate("key".to_owned(), "secret".to_owned())
+            .with_retry_policy(RetryPolicy::default()),
+    );
+    assert!(matches!(
+        api.request(order).await,
+        Err(Error::RateLimited(..))
+    ));
+    assert_eq!(8, mock.requests().len());
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_rate_limits() {
+    let mock = MockExchange::start().await.unwrap();
+    let limit = RateLimit {
+        requests: 2,
+        per: Duration::from_millis(200),
+    };
+    let api = Rest::new(
+        mock.options()
+            .authenticate("key".to_owned(), "secret".to_owned())
+            .with_rate_limits(RateLimits {
+                orders: limit,
+                other: limit,
+            }),
+    );
+
+    // Clones share the same buckets, so only the first two requests are sent
+    // right away, and the remaining four are spread over 400ms
+    let start = Instant::now();
+    let requests = (0..6).map(|_| {
+        let api = api.clone();
+        async move { api.request(GetMarkets {}).await }
+    });
+    for result in futures::future::join_all(requests).await {
+        result.unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(390));
+
+    // Orders have a bucket of their own
+    let start = Instant::now();
+    api.request(CancelOrderByClientId::new("a"))
+        .await
+        .unwrap_err();
+    api.request(CancelOrderByClientId::new("b"))
+        .await
+        .unwrap_err();
+    assert!(start.elapsed() < Duration::from_millis(50));
+    api.request(CancelOrderByClientId::new("c"))
+        .await
+        .unwrap_err();
+    assert!(start.elapsed() >= Duration::from_millis(90));
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_paginate() {
+    let (mock, api) = init_mock_api().await;
+    let t0 = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+
+    // 250 trades 400ms apart, more than two pages of 100
+    mock.add_trades(
+        "BTC-PERP",
+        (0..250)
+            .map(|i| {
+                let time = t0 + chrono::Duration::milliseconds(400 * i);
+                trade(i as Id, Side::Buy, dec!(20000), dec!(1), time)
+            })
+            .collect(),
+    );
+
+    let trades: Vec<_> = api
+        .paginate(GetTrades::new("BTC-PERP"))
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(250, trades.len());
+    assert!(trades.windows(2).all(|w| w[0].time > w[1].time));
+
+    let trades: Vec<_> = api
+        .paginate(GetTrades {
+            start_time: Some(t0 + chrono::Duration::seconds(10)),
+            end_time: Some(t0 + chrono::Duration::seconds(90)),
+            ..GetTrades::new("BTC-PERP")
+        })
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(
+        (25..=225).rev().collect::<Vec<Id>>(),
+        trades.iter().map(|t| t.id).collect::<Vec<_>>()
+    );
+
+    // Candles are returned oldest first by the API
+    mock.add_candles(
+        "BTC-PERP",
+        Resolution::Minute,
+        (0..3000)
+            .map(|i| Candle {
+                close: dec!(1),
+                high: dec!(1),
+                low: dec!(1),
+                open: dec!(1),
+                volume: dec!(1),
+                start_time: t0 + chrono::Duration::minutes(i),
+            })
+            .collect(),
+    );
+    let candles: Vec<_> = api
+        .paginate(GetHistoricalPrices::new("BTC-PERP", Resolution::Minute))
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(3000, candles.len());
+    assert_eq!(t0, candles.last().unwrap().start_time);
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_paginate_same_second() {
+    let (mock, api) = init_mock_api().await;
+    let t0 = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+
+    // 250 trades within a single second, more than two pages of 100, after
+    // one trade per second before it
+    mock.add_trades(
+        "BTC-PERP",
+        (0..5)
+            .map(|i| t0 + chrono::Duration::seconds(i))
+            .chain((0..250).map(|i| t0 + chrono::Duration::milliseconds(5000 + i)))
+            .enumerate()
+            .map(|(i, time)| trade(i as Id, Side::Buy, dec!(20000), dec!(1), time))
+            .collect(),
+    );
+
+    let trades: Vec<_> = api
+        .paginate(GetTrades::new("BTC-PERP"))
+        .try_collect()
+        .await
+        .unwrap();
+    // The API cannot page within a second, so only the newest page of it is
+    // returned before moving on to the earlier seconds
+    assert_eq!(
+        (0..=5).chain(155..255).rev().collect::<Vec<Id>>(),
+        trades.iter().map(|t| t.id).collect::<Vec<_>>()
+    );
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_with_client() {
+    let mock = MockExchange::start().await.unwrap();
+    mock.set_credentials("key", "secret");
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+    let api = Rest::with_client(
+        mock.options()
+            .authenticate("key".to_owned(), "secret".to_owned())
+            .subaccount("sub".to_owned()),
+        client,
+    )
+    .unwrap();
+    api.request(GetPositions {}).await.unwrap();
+
+    let request = mock.requests().pop().unwrap();
+    assert_eq!(request.headers["FTX-KEY"], "key");
+    assert_eq!(request.headers["FTX-SUBACCOUNT"], "sub");
+}
+
+#[test]
+fn invalid_options() {
+    match Rest::try_new(
+        Options::default().authenticate("invalid\nkey".to_owned(), "secret".to_owned()),
+    ) {
+        Err(Error::Config(ConfigError::InvalidHeader(header))) => assert_eq!(header, "FTX-KEY"),
+        other => panic!("Expected configuration error, got {:?}", other),
+    }
+}
+
+#[test]
+fn invalid_rate_limit_without_requests() {
+    let limit = RateLimit {
+        requests: 0,
+        per: Duration::from_secs(1),
+    };
+    let options = Options::default().with_rate_limits(RateLimits {
+        orders: limit,
+        ..Default::default()
+    });
+    match Rest::try_new(options) {
+        Err(Error::Config(ConfigError::InvalidRateLimit(limit))) => assert_eq!(limit.requests, 0),
+        other => panic!("Expected configuration error, got {:?}", other),
+    }
+}
+
+#[test]
+fn invalid_rate_limit_without_period() {
+    let limit = RateLimit {
+        requests: 30,
+        per: Duration::from_secs(0),
+    };
+    let options = Options::default().with_rate_limits(RateLimits {
+        other: limit,
+        ..Default::default()
+    });
+    match Rest::try_new(options) {
+        Err(Error::Config(ConfigError::InvalidRateLimit(limit))) => assert!(limit.per.is_zero()),
+        other => panic!("Expected configuration error, got {:?}", other),
+    }
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_market_rules() {
+    let (mock, api) = init_mock_api().await;
+    let mut btc_usd = market("BTC/USD", dec!(0.5), dec!(0.001));
+    btc_usd.min_provide_size = dec!(0.01);
+    mock.add_market(btc_usd);
+
+    let rules = MarketRules::fetch(&api).await.unwrap();
+    assert_eq!(
+        rules.round_price("BTC/USD", dec!(100.74)).unwrap(),
+        dec!(100.5)
+    );
+    assert_eq!(
+        rules.round_price("BTC/USD", dec!(100.75)).unwrap(),
+        dec!(101)
+    );
+    assert_eq!(
+        rules.round_size("BTC/USD", dec!(0.0129)).unwrap(),
+        dec!(0.012)
+    );
+
+    let order = PlaceOrder {
+        market: "BTC/USD",
+        side: Side::Buy,
+        price: Some(dec!(100.5)),
+        r#type: OrderType::Limit,
+        size: dec!(0.012),
+        ..Default::default()
+    };
+    rules.validate(&order).unwrap();
+
+    assert!(matches!(
+        rules.validate(&PlaceOrder {
+            price: None,
+            ..order.clone()
+        }),
+        Err(Error::PlacingLimitOrderRequiresPrice)
+    ));
+    assert!(matches!(
+        rules.validate(&PlaceOrder {
+            price: Some(dec!(100.2)),
+            ..order.clone()
+        }),
+        Err(Error::InvalidPriceIncrement { .. })
+    ));
+    assert!(matches!(
+        rules.validate(&PlaceOrder {
+            size: dec!(0.0125),
+            ..order.clone()
+        }),
+        Err(Error::InvalidSizeIncrement { .. })
+    ));
+    assert!(matches!(
+        rules.validate(&PlaceOrder {
+            size: dec!(0.005),
+            ..order.clone()
+        }),
+        Err(Error::SizeBelowMinimum { .. })
+    ));
+    assert!(matches!(
+        rules.validate(&PlaceOrder {
+            market: "ETH/USD",
+            ..order
+        }),
+        Err(Error::UnknownMarket(market)) if market == "ETH/USD"
+    ));
+}
+
+fn candle(start_time: DateTime<Utc>, price: Decimal, volume: Decimal) -> Candle {
+    Candle {
+        close: price,
+        high: price,
+        low: price,
+        open: price,
+        volume,
+        start_time,
+    }
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_candle_service() {
+    let (mock, api) = init_mock_api().await;
+    // A multiple of a minute, like the start times of FTX candles
+    let t0 = Utc.timestamp_opt(1_600_000_020, 0).unwrap();
+    let minute = |i: i64| t0 + chrono::Duration::minutes(i);
+
+    // Minutes 100 to 109 had no trades
+    mock.add_candles(
+        "BTC-PERP",
+        Resolution::Minute,
+        (0..300)
+            .filter(|i| !(100..110).contains(i))
+            .map(|i| candle(minute(i), Decimal::from(i), dec!(1)))
+            .collect(),
+    );
+    let requests = mock.requests().len();
+
+    let service = CandleService::new(api).with_chunk_size(50);
+    let candles = service
+        .fetch("BTC-PERP", Resolution::Minute, t0, minute(300))
+        .await
+        .unwrap();
+    assert_eq!(6, mock.requests().len() - requests);
+    assert_eq!(300, candles.len());
+    assert!(candles
+        .iter()
+        .enumerate()
+        .all(|(i, candle)| candle.start_time == minute(i as i64)));
+    assert_eq!(dec!(99), candles[105].close);
+    assert_eq!(Decimal::ZERO, candles[105].volume);
+
+    let candles = service
+        .clone()
+        .with_gap_filling(false)
+        .fetch("BTC-PERP", Resolution::Minute, minute(90), minute(120))
+        .await
+        .unwrap();
+    assert_eq!(20, candles.len());
+
+    // Less than a second of the last chunk is left, which still contains
+    // the candle starting at minute 250
+    let requests = mock.requests().len();
+    let candles = service
+        .fetch(
+            "BTC-PERP",
+            Resolution::Minute,
+            minute(200),
+            minute(250) + chrono::Duration::milliseconds(500),
+        )
+        .await
+        .unwrap();
+    assert_eq!(51, candles.len());
+    assert!(mock.requests()[requests..].iter().all(|request| {
+        let params: HashMap<String, f64> = serde_qs::from_str(&request.query).unwrap();
+        params["end_time"] >= params["start_time"]
+    }));
+
+    // Seven minute candles are not offered by FTX
+    let candles = service
+        .fetch_resampled(
+            "BTC-PERP",
+            Resolution::Minute,
+            Duration::from_secs(7 * 60),
+            minute(10),
+            minute(80),
+        )
+        .await
+        .unwrap();
+    assert!(candles
+        .iter()
+        .all(|candle| candle.start_time.timestamp() % (7 * 60) == 0));
+    assert_eq!(dec!(7), candles[1].volume);
+
+    assert!(matches!(
+        service
+            .fetch_resampled(
+                "BTC-PERP",
+                Resolution::Minute,
+                Duration::from_secs(90),
+                t0,
+                minute(10),
+            )
+            .await,
+        Err(Error::InvalidCandleInterval { .. })
+    ));
+}
+
+#[test]
+fn resample_candles() {
+    let t0 = Utc.timestamp_opt(1_600_000_215, 0).unwrap();
+    let seconds = |i: i64| t0 + chrono::Duration::seconds(i * 15);
+    let candles = fill_gaps(
+        &[
+            candle(seconds(0), dec!(10), dec!(1)),
+            candle(seconds(1), dec!(12), dec!(2)),
+            candle(seconds(4), dec!(8), dec!(3)),
+        ],
+        chrono::Duration::seconds(15),
+    );
+    assert_eq!(5, candles.len());
+    assert_eq!(dec!(12), candles[2].open);
+    assert_eq!(Decimal::ZERO, candles[3].volume);
+
+    // The buckets are aligned to the epoch rather than the first candle
+    let resampled = resample(&candles, chrono::Duration::seconds(45));
+    assert_eq!(2, resampled.len());
+    assert_eq!(t0 - chrono::Duration::seconds(15), resampled[0].start_time);
+    assert_eq!(
+        (dec!(10), dec!(12), dec!(10), dec!(12), dec!(3)),
+        (
+            resampled[0].open,
+            resampled[0].high,
+            resampled[0].low,
+            resampled[0].close,
+            resampled[0].volume
+        )
+    );
+    assert_eq!(
+        (dec!(12), dec!(8), dec!(8), dec!(3)),
+        (
+            resampled[1].open,
+            resampled[1].low,
+            resampled[1].close,
+            resampled[1].volume
+        )
+    );
+}
+
+#[cfg(feature = "ws")]
+fn bid(market: &str, price: Decimal) -> PlaceOrder<'_> {
+    PlaceOrder {
+        market,
+        side: Side::Buy,
+        price: Some(price),
+        r#type: OrderType::Limit,
+        size: dec!(1),
+        post_only: true,
+        ..Default::default()
+    }
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_batch_orders() {
+    let (mock, api) = init_mock_api().await;
+    mock.add_market(market("BTC-PERP", dec!(1), dec!(0.0001)));
+
+    let prices: Vec<_> = (1..=20).map(Decimal::from).collect();
+    let results = api
+        .place_orders(prices.iter().map(|price| bid("BTC-PERP", *price)).collect())
+        .await;
+    // Results are returned in the order of the input
+    let placed: Vec<_> = results.into_iter().map(Result::unwrap).collect();
+    assert_eq!(
+        prices,
+        placed
+            .iter()
+            .map(|order| order.price.unwrap())
+            .collect::<Vec<_>>()
+    );
+
+    let ids: Vec<_> = placed.iter().map(|order| order.id).collect();
+    let results = api.cancel_orders(vec![ids[0], 0, ids[1]]).await;
+    assert!(results[0].is_ok());
+    assert!(matches!(results[1], Err(Error::OrderNotFound(..))));
+    assert!(results[2].is_ok());
+
+    let results = api
+        .place_orders(vec![bid("BTC-PERP", dec!(1)), bid("ETH-PERP", dec!(1))])
+        .await;
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err());
+    assert_eq!(
+        19,
+        api.request(GetOpenOrders::all_market())
+            .await
+            .unwrap()
+            .len()
+    );
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_batch_orders_all_or_nothing() {
+    let (mock, api) = init_mock_api().await;
+    mock.add_market(market("BTC-PERP", dec!(1), dec!(0.0001)));
+
+    let orders = api
+        .place_orders_all_or_nothing(vec![bid("BTC-PERP", dec!(1)), bid("BTC-PERP", dec!(2))])
+        .await
+        .unwrap();
+    assert_eq!(2, orders.len());
+    api.cancel_orders(orders.iter().map(|order| order.id).collect())
+        .await;
+
+    match api
+        .place_orders_all_or_nothing(vec![
+            bid("BTC-PERP", dec!(1)),
+            bid("ETH-PERP", dec!(1)),
+            bid("BTC-PERP", dec!(2)),
+        ])
+        .await
+    {
+        Err(Error::BatchFailed {
+            index,
+            source,
+            cancelled,
+            remaining,
+        }) => {
+            assert_eq!(1, index);
+            assert!(matches!(*source, Error::Api(StatusCode::NOT_FOUND, _)));
+            assert_eq!(2, cancelled.len());
+            assert!(remaining.is_empty());
+        }
+        other => panic!("Expected a failed batch, got {:?}", other),
+    }
+    assert!(api
+        .request(GetOpenOrders::all_market())
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_trigger_orders() {
+    let (mock, api) = init_mock_api().await;
+
+    // Sanitized responses from FTX API.
+    let trailing_stop = serde_json::json!({
+        "createdAt": "2019-03-05T09:56:55.728933+00:00",
+        "error": null,
+        "future": "XRP-PERP",
+        "id": 50001,
+        "market": "XRP-PERP",
+        "orderId": null,
+        "orderPrice": null,
+        "reduceOnly": false,
+        "side": "buy",
+        "size": 0.003,
+        "status": "open",
+        "trailStart": 0.3,
+        "trailValue": 0.05,
+        "triggerPrice": 0.35,
+        "triggeredAt": null,
+        "type": "trailing_stop",
+        "orderType": "market",
+        "filledSize": 0,
+        "avgFillPrice": null,
+        "retryUntilFilled": false
+    });
+    let triggered_stop = serde_json::json!({
+        "createdAt": "2019-03-05T09:56:55.728933+00:00",
+        "error": null,
+        "future": "XRP-PERP",
+        "id": 50000,
+        "market": "XRP-PERP",
+        "orderId": 38066650,
+        "orderPrice": 0.3,
+        "reduceOnly": true,
+        "side": "sell",
+        "size": 4,
+        "status": "triggered",
+        "trailStart": null,
+        "trailValue": null,
+        "triggerPrice": 0.31,
+        "triggeredAt": "2019-03-05T10:01:02.123456+00:00",
+        "type": "stop",
+        "orderType": "limit",
+        "filledSize": 4,
+        "avgFillPrice": 0.3,
+        "retryUntilFilled": false,
+        "cancelReason": null
+    });
+    mock.respond(
+        Method::GET,
+        "/conditional_orders",
+        MockResponse::success([&trailing_stop]),
+    );
+    mock.respond(
+        Method::GET,
+        "/conditional_orders/history",
+        MockResponse::success([&triggered_stop]),
+    );
+    mock.respond(
+        Method::GET,
+        "/conditional_orders/50000/triggers",
+        MockResponse::success(serde_json::json!([{
+            "error": null,
+            "filledSize": 4.0,
+            "orderSize": 4.0,
+            "orderId": 38066650,
+            "time": "2020-01-19T09:23:36.570904+00:00"
+        }])),
+    );
+    mock.respond(
+        Method::POST,
+        "/conditional_orders/50001/modify",
+        MockResponse::success(&trailing_stop),
+    );
+    mock.respond(
+        Method::DELETE,
+        "/conditional_orders/50001",
+        MockResponse::success("Order queued for cancellation"),
+    );
+
+    let orders = api
+        .request(GetTriggerOrders {
+            r#type: Some(TriggerType::TrailingStop),
+            ..GetTriggerOrders::with_market("XRP-PERP")
+        })
+        .await
+        .unwrap();
+    assert_eq!(1, orders.len());
+    assert_eq!(TriggerType::TrailingStop, orders[0].r#type);
+    assert_eq!(TriggerOrderStatus::Open, orders[0].status);
+    assert_eq!(Some(dec!(0.05)), orders[0].trail_value);
+    assert_eq!(None, orders[0].triggered_at);
+
+    let start_time = Utc.timestamp_opt(1_551_700_000, 0).unwrap();
+    let history = api
+        .request(GetTriggerOrderHistory {
+            market: Some("XRP-PERP"),
+            order_type: Some(OrderType::Limit),
+            start_time: Some(start_time),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(TriggerOrderStatus::Triggered, history[0].status);
+    assert_eq!(OrderType::Limit, history[0].order_type);
+    assert_eq!(Some(38066650), history[0].order_id);
+    assert_eq!(
+        Some(Utc.timestamp_opt(1_551_780_062, 123_456_000).unwrap()),
+        history[0].triggered_at
+    );
+
+    let triggers = api.request(GetTriggers::new(50000)).await.unwrap();
+    assert_eq!(Some(38066650), triggers[0].order_id);
+    assert_eq!(Some(dec!(4)), triggers[0].filled_size);
+
+    api.request(ModifyTriggerOrder {
+        id: 50001,
+        size: Some(dec!(0.004)),
+        trail_value: Some(dec!(0.06)),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    api.request(CancelTriggerOrder::new(50001)).await.unwrap();
+
+    let requests = mock.requests();
+    assert_eq!("market=XRP-PERP&type=trailing_stop", requests[0].query);
+    assert_eq!(
+        "market=XRP-PERP&orderType=limit&start_time=1551700000",
+        requests[1].query
+    );
+    assert_eq!(
+        serde_json::json!({"size": "0.004", "trailValue": "0.06"}),
+        serde_json::from_str::<serde_json::Value>(&requests[3].body).unwrap()
+    );
+    assert_eq!(Method::DELETE, requests[4].method);
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_order_builder() {
+    let (mock, api) = init_mock_api().await;
+    mock.add_market(market("BTC-PERP", dec!(1), dec!(0.001)));
+
+    let order = api
+        .request(
+            Order::limit("BTC-PERP", Side::Buy, dec!(20000), dec!(0.1))
+                .post_only()
+                .client_id("entry")
+                .build(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(OrderType::Limit, order.r#type);
+    assert_eq!(Some(dec!(20000)), order.price);
+    assert_eq!(Some(true), order.post_only);
+    assert_eq!(Some(false), order.ioc);
+    assert_eq!(Some("entry"), order.client_id.as_deref());
+
+    let order = api
+        .request(
+            Order::market("BTC-PERP", Side::Sell, dec!(0.1))
+                .reduce_only()
+                .build(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(OrderType::Market, order.r#type);
+    let body: serde_json::Value = serde_json::from_str(&mock.requests()[1].body).unwrap();
+    assert_eq!(serde_json::Value::Null, body["price"]);
+    assert_eq!(serde_json::Value::Bool(true), body["reduceOnly"]);
+
+    let stop = Order::stop("BTC-PERP", Side::Sell, dec!(19000), dec!(0.1))
+        .limit(dec!(18900))
+        .reduce_only()
+        .build();
+    assert_eq!(
+        serde_json::json!({
+            "market": "BTC-PERP",
+            "side": "sell",
+            "size": "0.1",
+            "type": "stop",
+            "triggerPrice": "19000",
+            "orderPrice": "18900",
+            "reduceOnly": true,
+        }),
+        serde_json::to_value(&stop).unwrap()
+    );
+
+    let trailing_stop: PlaceTriggerOrder =
+        Order::trailing_stop("BTC-PERP", Side::Sell, dec!(-100), dec!(0.1)).into();
+    assert_eq!(
+        serde_json::json!({
+            "market": "BTC-PERP",
+            "side": "sell",
+            "size": "0.1",
+            "type": "trailingStop",
+            "trailValue": "-100",
+        }),
+        serde_json::to_value(&trailing_stop).unwrap()
+    );
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn mock_twap_orders() {
+    let (mock, api) = init_mock_api().await;
+
+    let twap = serde_json::json!({
+        "id": 1234,
+        "market": "BTC-PERP",
+        "side": "buy",
+        "size": 5,
+        "status": "running",
+        "filledSize": 1.2,
+        "avgFillPrice": 20100.5,
+        "durationSeconds": 3600,
+        "randomizeSize": true,
+        "maxSpread": null,
+        "maxDistance": 0.01,
+        "maxIndividualOrderSize": null,
+        "reduceOnly": false,
+        "createdAt": "2022-06-01T10:00:00+00:00",
+        "endTime": "2022-06-01T11:00:00+00:00"
+    });
+    mock.respond(Method::POST, "/twap_orders", MockResponse::success(&twap));
+    mock.respond(Method::GET, "/twap_orders", MockResponse::success([&twap]));
+    mock.respond(
+        Method::DELETE,
+        "/twap_orders/1234",
+        MockResponse::success("Order queued for cancellation"),
+    );
+    mock.respond(
+        Method::GET,
+        "/twap_orders/1234/fills",
+        MockResponse::success([crate::mock::fill(
+            1,
+            "BTC-PERP",
+            Side::Buy,
+            dec!(20100.5),
+            dec!(1.2),
+            Utc.timestamp_opt(1_654_077_600, 0).unwrap(),
+        )]),
+    );
+
+    let order = api
+        .request(PlaceTwapOrder {
+            market: "BTC-PERP",
+            side: Side::Buy,
+            size: dec!(5),
+            duration_seconds: 3600,
+            randomize_size: true,
+            max_distance: Some(dec!(0.01)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(TwapOrderStatus::Running, order.status);
+    assert_eq!(Some(dec!(1.2)), order.filled_size);
+    assert_eq!(
+        Some(Utc.timestamp_opt(1_654_081_200, 0).unwrap()),
+        order.end_time
+    );
+
+    let orders = api
+        .request(GetTwapOrders {
+            status: Some(TwapOrderStatus::Running),
+            ..GetTwapOrders::with_market("BTC-PERP")
+        })
+        .await
+        .unwrap();
+    assert_eq!(1234, orders[0].id);
+
+    let fills = api.request(GetTwapOrderFills::new(1234)).await.unwrap();
+    assert_eq!(dec!(1.2), fills[0].size);
+    api.request(CancelTwapOrder::new(1234)).await.unwrap();
+
+    let requests = mock.requests();
+    assert_eq!(
+        serde_json::json!({
+            "market": "BTC-PERP",
+            "side": "buy",
+            "size": "5",
+            "durationSeconds": 3600,
+            "randomizeSize": true,
+            "maxDistance": "0.01",
+            "reduceOnly": false,
+        }),
+        serde_json::from_str::<serde_json::Value>(&requests[0].body).unwrap()
+    );
+    assert_eq!("market=BTC-PERP&status=running", requests[1].query);
+}
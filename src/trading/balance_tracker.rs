@@ -0,0 +1,229 @@
+use super::recent_ids::RecentIds;
+use super::Result;
+use crate::rest::{
+    Balance, Coin, DepositStatus, GetWalletBalances, Rest, Side, Transfer,
+    TransferBetweenSubaccounts, WalletBalance, WalletDeposit, WalletWithdrawal, WithdrawStatus,
+};
+use crate::ws::{Data, Fill};
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+
+/// The name FTX uses for the main account in transfers between subaccounts
+const MAIN_ACCOUNT: &str = "main";
+
+/// A difference between the tracked and the actual balance of a coin,
+/// found by `BalanceTracker::reconcile`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BalanceDrift {
+    pub coin: Coin,
+    /// The balance derived from the applied deltas
+    pub tracked: Decimal,
+    /// The balance reported by the API
+    pub actual: Decimal,
+}
+
+impl BalanceDrift {
+    pub fn difference(&self) -> Decimal {
+        self.actual - self.tracked
+    }
+}
+
+/// Maintains the total balance of every coin between snapshots of
+/// `GetWalletBalances` or `GetSubaccountBalances`.
+///
+/// Balances change by the deltas of spot fills and fees, transfers between
+/// subaccounts, deposits and withdrawals. Other changes, like funding
+/// payments or the settlement of futures PnL, are only picked up by
+/// reconciling against the API, which should therefore happen
+/// periodically. Fills, deposits and transfers are identified by their ID,
+/// so that they are only applied once. Only the IDs of the latest 10,000 of
+/// each are remembered.
+#[derive(Clone, Debug)]
+pub struct BalanceTracker {
+    /// The subaccount whose balances are tracked, the main account if `None`
+    subaccount: Option<String>,
+    balances: HashMap<Coin, Decimal>,
+    tolerance: Decimal,
+    applied_fills: RecentIds,
+    applied_deposits: RecentIds,
+    applied_transfers: RecentIds,
+}
+
+impl BalanceTracker {
+    /// Tracks the balances of the given subaccount, or of the main account.
+    pub fn new(subaccount: Option<&str>) -> Self {
+        Self {
+            subaccount: subaccount.map(ToOwned::to_owned),
+            balances: HashMap::new(),
+            tolerance: Decimal::ZERO,
+            applied_fills: RecentIds::default(),
+            applied_deposits: RecentIds::default(),
+            applied_transfers: RecentIds::default(),
+        }
+    }
+
+    /// Creates a tracker for the subaccount of `rest`, seeded with its
+    /// current balances.
+    pub async fn fetch(rest: &Rest) -> Result<Self> {
+        let mut tracker = Self::new(rest.subaccount());
+        tracker.seed(&rest.request(GetWalletBalances {}).await?);
+        Ok(tracker)
+    }
+
+    /// Differences of up to `tolerance` in any coin are not reported by
+    /// `reconcile`
+    #[must_use]
+    pub fn with_tolerance(mut self, tolerance: Decimal) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Replaces all balances with a snapshot of `GetWalletBalances`.
+    pub fn seed(&mut self, balances: &[WalletBalance]) {
+        self.balances = totals(balances.iter().map(|b| (&b.coin, b.total)));
+    }
+
+    /// Replaces all balances with a snapshot of `GetSubaccountBalances`.
+    pub fn seed_subaccount(&mut self, balances: &[Balance]) {
+        self.balances = totals(balances.iter().map(|b| (&b.coin, b.total)));
+    }
+
+    pub fn balance(&self, coin: &str) -> Decimal {
+        self.balances.get(coin).copied().unwrap_or_default()
+    }
+
+    pub fn balances(&self) -> &HashMap<Coin, Decimal> {
+        &self.balances
+    }
+
+    fn add(&mut self, coin: &str, delta: Decimal) {
+        *self.balances.entry(coin.to_owned()).or_default() += delta;
+    }
+
+    /// Applies the fee of a fill and, for spot fills, the exchanged base
+    /// and quote currency. Returns whether it was applied or had been
+    /// already.
+    pub fn apply_fill(&mut self, fill: &Fill) -> bool {
+        if !self.applied_fills.insert(fill.id) {
+            return false;
+        }
+
+        if let (Some(base), Some(quote)) = (&fill.base_currency, &fill.quote_currency) {
+            let (base_delta, quote_delta) = match fill.side {
+                Side::Buy => (fill.size, -fill.size * fill.price),
+                Side::Sell => (-fill.size, fill.size * fill.price),
+            };
+            self.add(base, base_delta);
+            self.add(quote, quote_delta);
+        }
+        self.add(&fill.fee_currency, -fill.fee);
+        true
+    }
+
+    /// Applies a transfer from or to the tracked account, as returned for
+    /// `request`.
+    pub fn apply_transfer(
+        &mut self,
+        request: &TransferBetweenSubaccounts<'_>,
+        transfer: &Transfer,
+    ) -> bool {
+        let account = self.subaccount.as_deref().unwrap_or(MAIN_ACCOUNT);
+        let delta = if request.source == account {
+            -transfer.size
+        } else if request.destination == account {
+            transfer.size
+        } else {
+            return false;
+        };
+        if !self.applied_transfers.insert(transfer.id) {
+            return false;
+        }
+
+        self.add(&transfer.coin, delta);
+        true
+    }
+
+    /// Applies a deposit once it has been credited.
+    pub fn apply_deposit(&mut self, deposit: &WalletDeposit) -> bool {
+        let size = match (deposit.status, deposit.size) {
+            (DepositStatus::Confirmed | DepositStatus::Complete, Some(size)) => size,
+            _ => return false,
+        };
+        if !self.applied_deposits.insert(deposit.id) {
+            return false;
+        }
+
+        // The fee is not included in the size
+        self.add(&deposit.coin, size);
+        true
+    }
+
+    /// Applies a withdrawal and its fee, unless it was cancelled.
+    /// Withdrawals have no ID, so every withdrawal must only be applied
+    /// once, e.g. right after requesting it.
+    pub fn apply_withdrawal(&mut self, withdrawal: &WalletWithdrawal) -> bool {
+        if withdrawal.status == WithdrawStatus::Cancelled {
+            return false;
+        }
+
+        let delta = -withdrawal.size - withdrawal.fee.unwrap_or_default();
+        self.add(&withdrawal.coin, delta);
+        true
+    }
+
+    /// Applies fills received from `Ws`.
+    pub fn update(&mut self, data: &Data) {
+        if let Data::Fill(fill) = data {
+            self.apply_fill(fill);
+        }
+    }
+
+    /// Compares the tracked balances with a snapshot of `GetWalletBalances`
+    /// and returns the coins that drifted apart by more than the tolerance.
+    /// The tracked balances are replaced by the snapshot afterwards.
+    pub fn reconcile(&mut self, balances: &[WalletBalance]) -> Vec<BalanceDrift> {
+        self.reconcile_totals(totals(balances.iter().map(|b| (&b.coin, b.total))))
+    }
+
+    /// Like `reconcile`, with a snapshot of `GetSubaccountBalances`.
+    pub fn reconcile_subaccount(&mut self, balances: &[Balance]) -> Vec<BalanceDrift> {
+        self.reconcile_totals(totals(balances.iter().map(|b| (&b.coin, b.total))))
+    }
+
+    /// Fetches the balances of the subaccount of `rest` and reconciles the
+    /// tracked balances with them.
+    pub async fn reconcile_with(&mut self, rest: &Rest) -> Result<Vec<BalanceDrift>> {
+        let balances = rest.request(GetWalletBalances {}).await?;
+        Ok(self.reconcile(&balances))
+    }
+
+    fn reconcile_totals(&mut self, actual: HashMap<Coin, Decimal>) -> Vec<BalanceDrift> {
+        let coins: HashSet<&Coin> = self.balances.keys().chain(actual.keys()).collect();
+        let mut drifts: Vec<_> = coins
+            .into_iter()
+            .map(|coin| BalanceDrift {
+                coin: coin.clone(),
+                tracked: self.balance(coin),
+                actual: actual.get(coin).copied().unwrap_or_default(),
+            })
+            .filter(|drift| drift.difference().abs() > self.tolerance)
+            .collect();
+        drifts.sort_by(|a, b| a.coin.cmp(&b.coin));
+
+        for drift in &drifts {
+            log::warn!(
+                "Balance of {} drifted by {}",
+                drift.coin,
+                drift.difference()
+            );
+        }
+        self.balances = actual;
+        drifts
+    }
+}
+
+fn totals<'a>(balances: impl Iterator<Item = (&'a Coin, Decimal)>) -> HashMap<Coin, Decimal> {
+    balances
+        .map(|(coin, total)| (coin.clone(), total))
+        .collect()
+}
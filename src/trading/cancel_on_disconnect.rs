@@ -0,0 +1,254 @@
+use super::{Error, Result};
+use crate::rest::{CancelAllOrder, GetOpenOrders, OrderInfo, OrderType, Rest, Side, Symbol};
+use crate::ws::{self, Data, Ws};
+use futures::{
+    future::BoxFuture,
+    ready,
+    task::{Context, Poll},
+    FutureExt, Stream, StreamExt,
+};
+use std::pin::Pin;
+
+/// The orders cancelled by a `CancelOnDisconnect` guard
+#[derive(Clone, Debug, Default)]
+pub struct CancelScope {
+    /// Only cancel orders of this market
+    pub market: Option<Symbol>,
+    /// Only cancel orders of this side
+    pub side: Option<Side>,
+    /// Only cancel limit orders, leaving e.g. conditional orders untouched
+    pub limit_orders_only: bool,
+}
+
+/// Why a `CancelOnDisconnect` guard cancelled orders
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CancelReason {
+    /// The websocket connection was closed or failed
+    ConnectionLost,
+    /// The server stopped answering pings, see `Ws::with_max_missed_pongs`
+    MissedPongs,
+    /// The guard was shut down or dropped
+    Shutdown,
+}
+
+/// What a `CancelOnDisconnect` guard cancelled
+#[derive(Clone, Debug)]
+pub struct CancelReport {
+    pub reason: CancelReason,
+    /// The orders within the scope that were open right before cancelling,
+    /// empty if they could not be fetched
+    pub orders: Vec<OrderInfo>,
+    /// The response of the API to the cancellation
+    pub message: String,
+}
+
+/// An event of a `CancelOnDisconnect` guard
+#[derive(Clone, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum GuardEvent {
+    /// Data received by the websocket
+    Data(Option<Symbol>, Data),
+    /// Orders were cancelled because the connection was lost
+    Cancelled(CancelReport),
+}
+
+type Cancellation = BoxFuture<'static, Result<CancelReport>>;
+
+/// Cancels all open orders when an authenticated websocket loses its
+/// connection, so that a crashed or disconnected strategy can not leave
+/// stale quotes behind.
+///
+/// The guard wraps the websocket and returns its data as
+/// `GuardEvent::Data`. Orders are cancelled through `Rest` when
+/// - the connection is lost, either as an error or as `Data::Disconnected`
+///   if the websocket reconnects automatically,
+/// - pongs are missed, if enabled with `Ws::with_max_missed_pongs`,
+/// - the guard is shut down with `shutdown`, or dropped while a Tokio
+///   runtime is available.
+///
+/// Every cancellation is reported as `GuardEvent::Cancelled`. Orders are
+/// cancelled once per lost connection: the guard stays disarmed until the
+/// websocket reconnects, and is armed again by `Data::Reconnected`.
+pub struct CancelOnDisconnect {
+    ws: Ws,
+    rest: Rest,
+    scope: CancelScope,
+    armed: bool,
+    /// Whether the orders were already cancelled for the lost connection
+    cancelled: bool,
+    cancelling: Option<Cancellation>,
+    /// The connection error to return once its cancellation was reported
+    error: Option<ws::Error>,
+}
+
+impl CancelOnDisconnect {
+    /// Guards the orders placed through `rest`. The websocket needs to be
+    /// authenticated, so that it belongs to the same account.
+    #[allow(unknown_lints, clippy::result_large_err)]
+    pub fn new(rest: Rest, ws: Ws) -> Result<Self> {
+        if !ws.is_authenticated() {
+            return Err(ws::Error::SocketNotAuthenticated.into());
+        }
+
+        Ok(Self {
+            ws,
+            rest,
+            scope: CancelScope::default(),
+            armed: true,
+            cancelled: false,
+            cancelling: None,
+            error: None,
+        })
+    }
+
+    /// Only cancel the orders within `scope` instead of all orders
+    #[must_use]
+    pub fn with_scope(mut self, scope: CancelScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// The guarded websocket, e.g. to subscribe to channels
+    pub fn ws(&mut self) -> &mut Ws {
+        &mut self.ws
+    }
+
+    /// Cancels the orders within the scope and disarms the guard, e.g. on a
+    /// graceful shutdown of the process.
+    pub async fn shutdown(mut self) -> Result<CancelReport> {
+        self.armed = false;
+        cancel(
+            self.rest.clone(),
+            self.scope.clone(),
+            CancelReason::Shutdown,
+        )
+        .await
+    }
+
+    /// Stops cancelling orders, e.g. to hand them over to another process.
+    pub fn disarm(&mut self) {
+        self.armed = false;
+    }
+
+    /// Whether orders are cancelled when the connection is lost, which is
+    /// not the case after `disarm` or until the websocket reconnected after
+    /// a cancellation.
+    pub fn is_armed(&self) -> bool {
+        self.armed && !self.cancelled
+    }
+
+    fn start_cancelling(&mut self, reason: CancelReason) {
+        if self.is_armed() {
+            log::warn!("Cancelling orders: {:?}", reason);
+            self.cancelling = Some(cancel(self.rest.clone(), self.scope.clone(), reason).boxed());
+            self.cancelled = true;
+        }
+    }
+}
+
+async fn cancel(rest: Rest, scope: CancelScope, reason: CancelReason) -> Result<CancelReport> {
+    let market = scope.market.as_deref();
+
+    // The orders are only fetched for the report, so failing to fetch them
+    // must not prevent cancelling them
+    let orders = match rest.request(GetOpenOrders { market }).await {
+        Ok(orders) => orders
+            .into_iter()
+            .filter(|order| scope.side.is_none() || scope.side == Some(order.side))
+            .filter(|order| !scope.limit_orders_only || order.r#type == OrderType::Limit)
+            .collect(),
+        Err(e) => {
+            log::warn!("Could not fetch open orders before cancelling: {}", e);
+            Vec::new()
+        }
+    };
+
+    let message = rest
+        .request(CancelAllOrder {
+            market,
+            side: scope.side,
+            limit_orders_only: if scope.limit_orders_only {
+                Some(true)
+            } else {
+                None
+            },
+            ..Default::default()
+        })
+        .await?;
+
+    Ok(CancelReport {
+        reason,
+        orders,
+        message,
+    })
+}
+
+impl Drop for CancelOnDisconnect {
+    fn drop(&mut self) {
+        // A pending cancellation is completed rather than started over
+        let cancellation = match self.cancelling.take() {
+            Some(cancelling) => cancelling,
+            None if self.is_armed() => cancel(
+                self.rest.clone(),
+                self.scope.clone(),
+                CancelReason::Shutdown,
+            )
+            .boxed(),
+            None => return,
+        };
+
+        // Without a runtime, e.g. while the process is exiting, there is
+        // nothing left to cancel the orders with
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                if let Err(e) = cancellation.await {
+                    log::error!("Could not cancel orders on drop: {}", e);
+                }
+            });
+        }
+    }
+}
+
+impl Stream for CancelOnDisconnect {
+    type Item = Result<GuardEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(cancelling) = self.cancelling.as_mut() {
+            let result = ready!(cancelling.poll_unpin(cx));
+            self.cancelling = None;
+            return Poll::Ready(Some(result.map(GuardEvent::Cancelled)));
+        }
+        if let Some(e) = self.error.take() {
+            return Poll::Ready(Some(Err(e.into())));
+        }
+
+        match ready!(self.ws.poll_next_unpin(cx)) {
+            Some(Ok((_, Data::Disconnected))) => {
+                let reason = if self.ws.lost_to_missed_pongs() {
+                    CancelReason::MissedPongs
+                } else {
+                    CancelReason::ConnectionLost
+                };
+                self.start_cancelling(reason);
+                Poll::Ready(Some(Ok(GuardEvent::Data(None, Data::Disconnected))))
+            }
+            Some(Ok((_, Data::Reconnected))) => {
+                self.cancelled = false;
+                Poll::Ready(Some(Ok(GuardEvent::Data(None, Data::Reconnected))))
+            }
+            Some(Ok((symbol, data))) => Poll::Ready(Some(Ok(GuardEvent::Data(symbol, data)))),
+            Some(Err(e)) if e.is_connection_lost() && self.is_armed() => {
+                let reason = match e {
+                    ws::Error::MissedPongs(_) => CancelReason::MissedPongs,
+                    _ => CancelReason::ConnectionLost,
+                };
+                self.start_cancelling(reason);
+                self.error = Some(e);
+                // Poll the cancellation right away
+                self.poll_next(cx)
+            }
+            Some(Err(e)) => Poll::Ready(Some(Err(Error::Ws(e)))),
+            None => Poll::Ready(None),
+        }
+    }
+}
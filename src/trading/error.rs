@@ -0,0 +1,19 @@
+use crate::{rest, ws};
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("stopped processing updates because the websocket connection was lost")]
+    Stopped,
+
+    #[error("an active order with client ID {0} exists already")]
+    DuplicateClientId(String),
+
+    #[error(transparent)]
+    Rest(#[from] rest::Error),
+
+    #[error(transparent)]
+    Ws(#[from] ws::Error),
+}
@@ -0,0 +1,21 @@
+//! This module provides building blocks for trading on top of the REST and
+//! Websocket APIs.
+
+mod balance_tracker;
+mod cancel_on_disconnect;
+mod error;
+mod order_manager;
+mod position_tracker;
+mod quote_ladder;
+mod recent_ids;
+mod slice_executor;
+#[cfg(test)]
+mod tests;
+
+pub use balance_tracker::*;
+pub use cancel_on_disconnect::*;
+pub use error::*;
+pub use order_manager::*;
+pub use position_tracker::*;
+pub use quote_ladder::*;
+pub use slice_executor::*;
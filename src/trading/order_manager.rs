@@ -0,0 +1,331 @@
+use super::{Error, Result};
+use crate::rest::{
+    self, CancelOrderByClientId, GetOrderByClientId, OrderInfo, OrderStatus, PlaceOrder, Rest,
+};
+use crate::ws::{Channel, Data, Ws};
+use chrono::Utc;
+use futures::StreamExt;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// The state of an order, combining the REST and websocket views of it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OrderState {
+    /// Queued for processing, but not processed yet
+    Pending,
+    /// Accepted and active without any fills
+    Accepted,
+    /// Active and partially filled
+    PartiallyFilled,
+    /// Closed after being filled completely
+    Filled,
+    /// Closed after being accepted or partially filled, e.g. because it was
+    /// cancelled
+    Cancelled,
+    /// Closed during processing without being accepted, e.g. a post-only
+    /// order that would have been executed as a taker order
+    Rejected,
+}
+
+impl OrderState {
+    pub fn is_closed(self) -> bool {
+        matches!(
+            self,
+            OrderState::Filled | OrderState::Cancelled | OrderState::Rejected
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Tracked {
+    /// `None` until the order has been queued
+    order: Option<OrderInfo>,
+    /// Whether the order has been reported as active at any point
+    accepted: bool,
+}
+
+impl Tracked {
+    /// Applies an update unless it is older than what is known already, and
+    /// returns whether it was applied.
+    fn update(&mut self, mut order: OrderInfo, from_ws: bool) -> bool {
+        // Over websockets, `New` confirms an order as active, which is
+        // `Open` over REST. See `OrderStatus`.
+        if from_ws && order.status == OrderStatus::New {
+            order.status = OrderStatus::Open;
+        }
+        if let Some(current) = &self.order {
+            if progress(&order) < progress(current) {
+                return false;
+            }
+        }
+
+        self.accepted |= order.status == OrderStatus::Open;
+        self.order = Some(order);
+        true
+    }
+
+    fn state(&self) -> OrderState {
+        let order = match &self.order {
+            Some(order) => order,
+            None => return OrderState::Pending,
+        };
+        let filled_size = order.filled_size.unwrap_or_default();
+
+        match order.status {
+            OrderStatus::New => OrderState::Pending,
+            OrderStatus::Open if filled_size.is_zero() => OrderState::Accepted,
+            OrderStatus::Open => OrderState::PartiallyFilled,
+            OrderStatus::Closed if filled_size >= order.size => OrderState::Filled,
+            OrderStatus::Closed if filled_size.is_zero() && !self.accepted => OrderState::Rejected,
+            OrderStatus::Closed => OrderState::Cancelled,
+        }
+    }
+}
+
+/// Orders only ever progress from new to open to closed, and their filled
+/// size only ever grows.
+fn progress(order: &OrderInfo) -> (u8, Decimal) {
+    let status = match order.status {
+        OrderStatus::New => 0,
+        OrderStatus::Open => 1,
+        OrderStatus::Closed => 2,
+    };
+    (status, order.filled_size.unwrap_or_default())
+}
+
+/// Active orders by client ID, `None` once updates are not processed anymore
+type Orders = Arc<Mutex<Option<HashMap<String, watch::Sender<Tracked>>>>>;
+
+fn update(orders: &Orders, order: OrderInfo, from_ws: bool) {
+    let mut orders = orders.lock().unwrap();
+    let orders = match orders.as_mut() {
+        Some(orders) => orders,
+        None => return,
+    };
+    let client_id = match &order.client_id {
+        Some(client_id) => client_id.clone(),
+        None => return,
+    };
+    let sender = match orders.get(&client_id) {
+        Some(sender) => sender,
+        // Not placed through the order manager
+        None => return,
+    };
+
+    sender.send_if_modified(|tracked| tracked.update(order, from_ws));
+    if sender.borrow().state().is_closed() {
+        orders.remove(&client_id);
+    }
+}
+
+async fn refresh(rest: &Rest, orders: &Orders, client_id: &str) -> rest::Result<()> {
+    let order = rest.request(GetOrderByClientId::new(client_id)).await?;
+    update(orders, order, false);
+    Ok(())
+}
+
+/// Processes order updates until the websocket connection is lost.
+async fn run(mut ws: Ws, rest: Rest, orders: Orders) {
+    while let Some(result) = ws.next().await {
+        match result {
+            Ok((_, Data::Order(order))) => update(&orders, order, true),
+            Ok((_, Data::Reconnected)) => {
+                // Updates may have been missed while disconnected
+                let client_ids: Vec<_> = match orders.lock().unwrap().as_ref() {
+                    Some(orders) => orders.keys().cloned().collect(),
+                    None => Vec::new(),
+                };
+                for client_id in client_ids {
+                    if let Err(e) = refresh(&rest, &orders, &client_id).await {
+                        log::warn!("Could not refresh order {}: {}", client_id, e);
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(e) if e.is_connection_lost() => {
+                log::error!("Order manager stopped: {}", e);
+                break;
+            }
+            Err(e) => log::warn!("Could not process order update: {}", e),
+        }
+    }
+
+    // Dropping the senders notifies all handles
+    orders.lock().unwrap().take();
+}
+
+/// Places orders over REST and follows their state over an authenticated
+/// websocket.
+///
+/// Every order is assigned a client ID, so that the websocket updates of an
+/// order can be matched to it before the REST API has responded. Both views
+/// of an order are reconciled, such that `OrderHandle`s only ever observe
+/// its state progressing.
+pub struct OrderManager {
+    rest: Rest,
+    orders: Orders,
+    /// Makes client IDs unique across instances
+    prefix: String,
+    next_client_id: AtomicU64,
+    task: JoinHandle<()>,
+}
+
+impl OrderManager {
+    /// Subscribes the websocket, which needs to be authenticated, to the
+    /// `Orders` channel and processes its updates in a background task.
+    /// Enable reconnecting on the websocket to keep processing updates when
+    /// the connection is lost.
+    pub async fn new(rest: Rest, mut ws: Ws) -> Result<Self> {
+        ws.subscribe(&[Channel::Orders]).await?;
+
+        let orders: Orders = Arc::new(Mutex::new(Some(HashMap::new())));
+        let task = tokio::spawn(run(ws, rest.clone(), orders.clone()));
+
+        Ok(Self {
+            rest,
+            orders,
+            prefix: format!("{:x}", Utc::now().timestamp_millis()),
+            next_client_id: AtomicU64::new(0),
+            task,
+        })
+    }
+
+    fn next_client_id(&self) -> String {
+        let id = self.next_client_id.fetch_add(1, Ordering::Relaxed);
+        format!("{}-{}", self.prefix, id)
+    }
+
+    /// Places an order, assigning it a client ID unless it has one already,
+    /// and returns a handle to follow its state.
+    pub async fn place(&self, order: PlaceOrder<'_>) -> Result<OrderHandle> {
+        let client_id = match order.client_id {
+            Some(client_id) => client_id.to_owned(),
+            None => self.next_client_id(),
+        };
+
+        // Track the order before placing it, since websocket updates may
+        // arrive before the REST response
+        let (sender, receiver) = watch::channel(Tracked::default());
+        match self.orders.lock().unwrap().as_mut() {
+            Some(orders) if orders.contains_key(&client_id) => {
+                return Err(Error::DuplicateClientId(client_id))
+            }
+            Some(orders) => orders.insert(client_id.clone(), sender),
+            None => return Err(Error::Stopped),
+        };
+
+        let placed = self
+            .rest
+            .request(PlaceOrder {
+                client_id: Some(&client_id),
+                ..order
+            })
+            .await;
+
+        match placed {
+            Ok(order) => {
+                update(&self.orders, order.clone(), false);
+                Ok(OrderHandle {
+                    client_id,
+                    receiver,
+                    placed: order,
+                })
+            }
+            Err(e) => {
+                if let Some(orders) = self.orders.lock().unwrap().as_mut() {
+                    orders.remove(&client_id);
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Requests the cancellation of an order. The handle reports the order
+    /// as cancelled once it has been closed.
+    pub async fn cancel(&self, handle: &OrderHandle) -> Result<()> {
+        self.rest
+            .request(CancelOrderByClientId::new(&handle.client_id))
+            .await?;
+        Ok(())
+    }
+
+    /// Fetches an order over REST and reconciles it with the websocket view,
+    /// e.g. if updates may have been missed.
+    pub async fn refresh(&self, handle: &OrderHandle) -> Result<OrderState> {
+        refresh(&self.rest, &self.orders, &handle.client_id).await?;
+        Ok(handle.state())
+    }
+
+    /// Whether updates are still being processed.
+    pub fn is_running(&self) -> bool {
+        self.orders.lock().unwrap().is_some()
+    }
+}
+
+impl Drop for OrderManager {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Follows the state of an order placed through an `OrderManager`.
+#[derive(Clone, Debug)]
+pub struct OrderHandle {
+    client_id: String,
+    receiver: watch::Receiver<Tracked>,
+    /// The response to placing the order, in case updates stopped before
+    /// it was tracked
+    placed: OrderInfo,
+}
+
+impl OrderHandle {
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// The latest known version of the order.
+    pub fn order(&self) -> OrderInfo {
+        match &self.receiver.borrow().order {
+            Some(order) => order.clone(),
+            None => self.placed.clone(),
+        }
+    }
+
+    pub fn state(&self) -> OrderState {
+        self.receiver.borrow().state()
+    }
+
+    /// Waits until the state of the order satisfies `predicate`.
+    pub async fn wait_for(&mut self, predicate: impl Fn(OrderState) -> bool) -> Result<OrderState> {
+        loop {
+            let state = self.state();
+            if predicate(state) {
+                return Ok(state);
+            }
+            if self.receiver.changed().await.is_err() {
+                // Closed orders are not updated anymore
+                let state = self.state();
+                return if predicate(state) {
+                    Ok(state)
+                } else {
+                    Err(Error::Stopped)
+                };
+            }
+        }
+    }
+
+    /// Waits until the order has been processed, i.e. it was accepted,
+    /// rejected, partially filled or closed.
+    pub async fn outcome(&mut self) -> Result<OrderState> {
+        self.wait_for(|state| state != OrderState::Pending).await
+    }
+
+    /// Waits until the order is closed.
+    pub async fn closed(&mut self) -> Result<OrderState> {
+        self.wait_for(OrderState::is_closed).await
+    }
+}
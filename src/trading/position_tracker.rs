@@ -0,0 +1,234 @@
+use super::recent_ids::RecentIds;
+use super::Result;
+use crate::rest::{Coin, GetPositions, Position, Rest, Side, Symbol};
+use crate::ws::{Data, Fill, Orderbook, Ticker};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// The position in a single future or market, as maintained by a
+/// `PositionTracker`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrackedPosition {
+    pub market: Symbol,
+    /// Positive when long, negative when short
+    pub net_size: Decimal,
+    /// The average price the open position was entered at, `None` while
+    /// flat
+    pub entry_price: Option<Decimal>,
+    pub realized_pnl: Decimal,
+    /// Fees paid by fee currency, negative for rebates
+    pub fees: HashMap<Coin, Decimal>,
+    /// The latest price the position was marked at
+    pub mark_price: Option<Decimal>,
+}
+
+impl TrackedPosition {
+    fn new(market: Symbol) -> Self {
+        Self {
+            market,
+            net_size: Decimal::ZERO,
+            entry_price: None,
+            realized_pnl: Decimal::ZERO,
+            fees: HashMap::new(),
+            mark_price: None,
+        }
+    }
+
+    /// The side of the open position, `None` while flat
+    pub fn side(&self) -> Option<Side> {
+        match self.net_size.cmp(&Decimal::ZERO) {
+            Ordering::Greater => Some(Side::Buy),
+            Ordering::Less => Some(Side::Sell),
+            Ordering::Equal => None,
+        }
+    }
+
+    /// The profit of the open position at the mark price, zero while flat
+    /// and `None` if the position has not been marked yet.
+    pub fn unrealized_pnl(&self) -> Option<Decimal> {
+        match self.entry_price {
+            Some(entry_price) => Some((self.mark_price? - entry_price) * self.net_size),
+            None => Some(Decimal::ZERO),
+        }
+    }
+
+    /// Applies a fill with average cost accounting: increasing the position
+    /// moves the entry price, while reducing it realizes profit at the entry
+    /// price.
+    fn apply(&mut self, fill: &Fill) {
+        let size = match fill.side {
+            Side::Buy => fill.size,
+            Side::Sell => -fill.size,
+        };
+        *self.fees.entry(fill.fee_currency.clone()).or_default() += fill.fee;
+
+        let entry_price = match self.entry_price {
+            // Reducing, closing or flipping the position
+            Some(entry_price) if self.net_size.is_sign_positive() != size.is_sign_positive() => {
+                entry_price
+            }
+            // Increasing the position
+            Some(entry_price) => {
+                let open = self.net_size.abs();
+                self.entry_price =
+                    Some((entry_price * open + fill.price * fill.size) / (open + fill.size));
+                self.net_size += size;
+                return;
+            }
+            // Opening a position
+            None => {
+                self.entry_price = Some(fill.price);
+                self.net_size += size;
+                return;
+            }
+        };
+
+        let closed = fill.size.min(self.net_size.abs());
+        let direction = if self.net_size.is_sign_positive() {
+            dec!(1)
+        } else {
+            dec!(-1)
+        };
+        self.realized_pnl += (fill.price - entry_price) * closed * direction;
+        self.net_size += size;
+
+        if self.net_size.is_zero() {
+            self.entry_price = None;
+        } else if fill.size > closed {
+            // The remainder opened a position on the other side
+            self.entry_price = Some(fill.price);
+        }
+    }
+}
+
+/// Maintains positions and PnL between snapshots of `GetPositions` by
+/// applying fills as they arrive, e.g. from the `Fills` channel.
+///
+/// Fills are identified by their ID, so that fills received over both
+/// websockets and REST are only applied once. Only the IDs of the latest
+/// 10,000 fills are remembered.
+#[derive(Clone, Debug, Default)]
+pub struct PositionTracker {
+    positions: HashMap<Symbol, TrackedPosition>,
+    applied: RecentIds,
+}
+
+impl PositionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a tracker seeded with the current positions.
+    pub async fn fetch(rest: &Rest) -> Result<Self> {
+        let mut tracker = Self::new();
+        tracker.seed(&rest.request(GetPositions {}).await?);
+        Ok(tracker)
+    }
+
+    /// Replaces the sizes, entry prices and realized PnL of the given
+    /// positions with a server snapshot. Fees and mark prices are kept.
+    ///
+    /// Open positions without an entry price are entered at their cost per
+    /// size, and skipped if they have no cost either.
+    pub fn seed(&mut self, positions: &[Position]) {
+        for position in positions {
+            let entry_price = if position.net_size.is_zero() {
+                None
+            } else {
+                match position.entry_price {
+                    Some(entry_price) => Some(entry_price),
+                    None if !position.cost.is_zero() => Some(position.cost / position.net_size),
+                    None => {
+                        log::warn!("Not seeding {} without entry price", position.future);
+                        continue;
+                    }
+                }
+            };
+
+            let tracked = self.entry(&position.future);
+            tracked.net_size = position.net_size;
+            tracked.entry_price = entry_price;
+            tracked.realized_pnl = position.realized_pnl;
+        }
+    }
+
+    fn entry(&mut self, market: &str) -> &mut TrackedPosition {
+        self.positions
+            .entry(market.to_owned())
+            .or_insert_with(|| TrackedPosition::new(market.to_owned()))
+    }
+
+    /// Applies a fill to the position of its future, or its market for spot
+    /// fills, and returns whether it was applied or had been already.
+    pub fn apply_fill(&mut self, fill: &Fill) -> bool {
+        let market = match fill.future.as_ref().or(fill.market.as_ref()) {
+            Some(market) => market.clone(),
+            None => return false,
+        };
+        if !self.applied.insert(fill.id) {
+            return false;
+        }
+
+        self.entry(&market).apply(fill);
+        true
+    }
+
+    /// Marks the position in `market` to `price`.
+    pub fn mark(&mut self, market: &str, price: Decimal) {
+        if let Some(position) = self.positions.get_mut(market) {
+            position.mark_price = Some(price);
+        }
+    }
+
+    /// Marks the position in `market` to the middle of the best bid and ask.
+    pub fn mark_ticker(&mut self, market: &str, ticker: &Ticker) {
+        self.mark(market, (ticker.bid + ticker.ask) / dec!(2));
+    }
+
+    /// Marks the position in the market of the orderbook to its mid price.
+    pub fn mark_orderbook(&mut self, orderbook: &Orderbook) {
+        if let Some(price) = orderbook.mid_price() {
+            self.mark(&orderbook.symbol, price);
+        }
+    }
+
+    /// Applies fills and marks positions with tickers received from `Ws`.
+    pub fn update(&mut self, symbol: Option<&str>, data: &Data) {
+        match (symbol, data) {
+            (_, Data::Fill(fill)) => {
+                self.apply_fill(fill);
+            }
+            (Some(symbol), Data::Ticker(ticker)) => self.mark_ticker(symbol, ticker),
+            _ => {}
+        }
+    }
+
+    pub fn position(&self, market: &str) -> Option<&TrackedPosition> {
+        self.positions.get(market)
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = &TrackedPosition> {
+        self.positions.values()
+    }
+
+    pub fn realized_pnl(&self) -> Decimal {
+        self.positions().map(|position| position.realized_pnl).sum()
+    }
+
+    /// The unrealized PnL of all positions, `None` if an open position has
+    /// not been marked yet.
+    pub fn unrealized_pnl(&self) -> Option<Decimal> {
+        self.positions().map(TrackedPosition::unrealized_pnl).sum()
+    }
+
+    /// The fees paid across all positions by fee currency.
+    pub fn fees(&self) -> HashMap<Coin, Decimal> {
+        let mut fees = HashMap::new();
+        for (coin, fee) in self.positions().flat_map(|position| &position.fees) {
+            *fees.entry(coin.clone()).or_default() += fee;
+        }
+        fees
+    }
+}
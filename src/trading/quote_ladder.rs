@@ -0,0 +1,285 @@
+use super::Result;
+use crate::rest::{
+    self, CancelOrder, GetOpenOrders, Id, ModifyOrder, OrderInfo, OrderStatus, OrderType,
+    PlaceOrder, Rest, Side,
+};
+use crate::ws::Data;
+use futures::future::join_all;
+use rust_decimal::Decimal;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// A limit order that should be open
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Quote {
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
+impl Quote {
+    pub fn new(side: Side, price: Decimal, size: Decimal) -> Self {
+        Self { side, price, size }
+    }
+}
+
+/// A request issued by a `QuoteLadder` to turn the open orders into the
+/// desired quotes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QuoteAction {
+    Place(Quote),
+    /// Move an open order to another price or size, which replaces it with
+    /// an order with a new ID
+    Modify {
+        id: Id,
+        quote: Quote,
+    },
+    Cancel(Id),
+}
+
+/// The outcome of `QuoteLadder::apply`
+#[derive(Debug, Default)]
+pub struct LadderReport {
+    pub placed: Vec<OrderInfo>,
+    /// The ID of each modified order and the order that replaced it
+    pub modified: Vec<(Id, OrderInfo)>,
+    pub cancelled: Vec<Id>,
+    /// The requests that failed
+    pub failed: Vec<(QuoteAction, rest::Error)>,
+}
+
+impl LadderReport {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Maintains ladders of limit orders in one or more markets.
+///
+/// Every call to `apply` takes the complete set of quotes that should be
+/// open in a market, compares it to the open orders of the ladder and issues
+/// the fewest requests to get there: quotes that are open already are left
+/// alone, other orders are modified to the remaining quotes, and only the
+/// difference is placed or cancelled. Modified orders are replaced by FTX
+/// with orders of a new ID, which the ladder keeps track of.
+///
+/// Only orders placed by the ladder, or loaded with `sync` or `seed`, are
+/// managed. Orders that are filled or cancelled elsewhere are removed with
+/// `update`, e.g. with the data of the orders channel.
+#[derive(Clone, Debug)]
+pub struct QuoteLadder {
+    rest: Rest,
+    post_only: bool,
+    orders: HashMap<Id, OrderInfo>,
+}
+
+impl QuoteLadder {
+    pub fn new(rest: Rest) -> Self {
+        Self {
+            rest,
+            post_only: false,
+            orders: HashMap::new(),
+        }
+    }
+
+    /// Places all quotes as post-only orders
+    #[must_use]
+    pub fn with_post_only(mut self, post_only: bool) -> Self {
+        self.post_only = post_only;
+        self
+    }
+
+    /// Replaces the orders of the ladder in `market` with its open limit
+    /// orders, e.g. after starting up or when the ladder might have missed
+    /// updates.
+    pub async fn sync(&mut self, market: &str) -> Result<()> {
+        let orders = self
+            .rest
+            .request(GetOpenOrders {
+                market: Some(market),
+            })
+            .await?;
+        self.orders.retain(|_, order| order.market != market);
+        self.seed(&orders);
+        Ok(())
+    }
+
+    /// Adds open limit orders to the ladder.
+    pub fn seed(&mut self, orders: &[OrderInfo]) {
+        for order in orders {
+            if order.r#type == OrderType::Limit && order.status != OrderStatus::Closed {
+                self.orders.insert(order.id, order.clone());
+            }
+        }
+    }
+
+    /// The open orders of the ladder in `market`
+    pub fn orders<'a>(&'a self, market: &'a str) -> impl Iterator<Item = &'a OrderInfo> + 'a {
+        self.orders
+            .values()
+            .filter(move |order| order.market == market)
+    }
+
+    /// Applies order updates received from `Ws`, so that orders which were
+    /// filled or cancelled are not managed anymore.
+    pub fn update(&mut self, data: &Data) {
+        if let Data::Order(order) = data {
+            if let Some(tracked) = self.orders.get_mut(&order.id) {
+                if order.status == OrderStatus::Closed {
+                    self.orders.remove(&order.id);
+                } else {
+                    *tracked = order.clone();
+                }
+            }
+        }
+    }
+
+    /// Returns the requests that turn the open orders of the ladder in
+    /// `market` into the given quotes.
+    pub fn diff(&self, market: &str, quotes: &[Quote]) -> Vec<QuoteAction> {
+        let mut actions = Vec::new();
+        for side in [Side::Buy, Side::Sell] {
+            let mut quotes: Vec<_> = quotes
+                .iter()
+                .filter(|quote| quote.side == side && quote.size > Decimal::ZERO)
+                .copied()
+                .collect();
+            let mut orders: Vec<_> = self
+                .orders(market)
+                .filter(|order| order.side == side)
+                .collect();
+
+            // Orders that match a quote exactly are kept as they are
+            quotes.retain(|quote| {
+                match orders.iter().position(|order| {
+                    order.price == Some(quote.price) && remaining_size(order) == quote.size
+                }) {
+                    Some(index) => {
+                        orders.swap_remove(index);
+                        false
+                    }
+                    None => true,
+                }
+            });
+
+            // The remaining orders are moved to the remaining quotes, best
+            // prices first
+            match side {
+                Side::Buy => {
+                    quotes.sort_by_key(|quote| Reverse(quote.price));
+                    orders.sort_by_key(|order| Reverse(order.price));
+                }
+                Side::Sell => {
+                    quotes.sort_by_key(|quote| quote.price);
+                    orders.sort_by_key(|order| order.price);
+                }
+            }
+            let mut orders = orders.into_iter();
+            for quote in quotes {
+                actions.push(match orders.next() {
+                    Some(order) => QuoteAction::Modify {
+                        id: order.id,
+                        quote,
+                    },
+                    None => QuoteAction::Place(quote),
+                });
+            }
+            actions.extend(orders.map(|order| QuoteAction::Cancel(order.id)));
+        }
+
+        actions
+    }
+
+    /// Issues the requests returned by `diff` concurrently and updates the
+    /// orders of the ladder with their results.
+    pub async fn apply(&mut self, market: &str, quotes: &[Quote]) -> LadderReport {
+        let actions = self.diff(market, quotes);
+        let results = join_all(
+            actions
+                .iter()
+                .map(|action| execute(&self.rest, market, self.post_only, action)),
+        )
+        .await;
+
+        let mut report = LadderReport::default();
+        for (action, result) in actions.into_iter().zip(results) {
+            match (&action, result) {
+                (QuoteAction::Place(_), Ok(Some(order))) => {
+                    self.insert(&order);
+                    report.placed.push(order);
+                }
+                (QuoteAction::Modify { id, .. }, Ok(Some(order))) => {
+                    self.orders.remove(id);
+                    self.insert(&order);
+                    report.modified.push((*id, order));
+                }
+                (QuoteAction::Cancel(id), Ok(_)) => {
+                    self.orders.remove(id);
+                    report.cancelled.push(*id);
+                }
+                (_, Ok(None)) => {}
+                (QuoteAction::Modify { id, .. } | QuoteAction::Cancel(id), Err(e))
+                    if is_closed(&e) =>
+                {
+                    // The order was filled or cancelled in the meantime
+                    self.orders.remove(id);
+                    report.failed.push((action, e));
+                }
+                (_, Err(e)) => report.failed.push((action, e)),
+            }
+        }
+
+        report
+    }
+
+    fn insert(&mut self, order: &OrderInfo) {
+        if order.status != OrderStatus::Closed {
+            self.orders.insert(order.id, order.clone());
+        }
+    }
+}
+
+fn remaining_size(order: &OrderInfo) -> Decimal {
+    order.remaining_size.unwrap_or(order.size)
+}
+
+fn is_closed(e: &rest::Error) -> bool {
+    matches!(
+        e,
+        rest::Error::OrderNotFound(..) | rest::Error::OrderAlreadyClosed(..)
+    )
+}
+
+/// Issues the request of an action, returning the resulting order for
+/// placements and modifications.
+async fn execute(
+    rest: &Rest,
+    market: &str,
+    post_only: bool,
+    action: &QuoteAction,
+) -> rest::Result<Option<OrderInfo>> {
+    match *action {
+        QuoteAction::Place(quote) => rest
+            .request(PlaceOrder {
+                market,
+                side: quote.side,
+                price: Some(quote.price),
+                r#type: OrderType::Limit,
+                size: quote.size,
+                post_only,
+                ..Default::default()
+            })
+            .await
+            .map(Some),
+        QuoteAction::Modify { id, quote } => rest
+            .request(ModifyOrder {
+                id,
+                price: Some(quote.price),
+                size: Some(quote.size),
+                client_id: None,
+            })
+            .await
+            .map(Some),
+        QuoteAction::Cancel(id) => rest.request(CancelOrder::new(id)).await.map(|_| None),
+    }
+}
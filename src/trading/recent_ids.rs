@@ -0,0 +1,32 @@
+use crate::rest::Id;
+use std::collections::{HashSet, VecDeque};
+
+/// How many IDs are remembered
+const CAPACITY: usize = 10_000;
+
+/// The IDs of the latest applied fills, deposits or transfers, to skip
+/// those received twice, e.g. over both websockets and REST. Only the
+/// latest 10,000 IDs are remembered, so that long running trackers do not
+/// grow without bound.
+#[derive(Clone, Debug, Default)]
+pub struct RecentIds {
+    ids: HashSet<Id>,
+    /// The IDs in `ids`, oldest first
+    order: VecDeque<Id>,
+}
+
+impl RecentIds {
+    /// Remembers `id` and returns whether it was new.
+    pub fn insert(&mut self, id: Id) -> bool {
+        if !self.ids.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,387 @@
+use super::Result;
+use crate::rest::{
+    self, CancelOrder, Id, MarketRules, OrderInfo, OrderStatus, OrderType, OrderbookView,
+    PlaceOrder, Rest, Side, Symbol,
+};
+use crate::ws::{Data, Orderbook, OrderbookAction};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// How a `SliceExecutor` splits its order into child orders
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Slicing {
+    /// Sends `slices` immediate-or-cancel orders at regular intervals over
+    /// `duration`, each for an equal share of the size that is left. Size
+    /// that is not filled is spread over the following slices, and retried
+    /// once per interval after the last one.
+    Twap { duration: Duration, slices: u32 },
+    /// Keeps a single limit order of at most `visible_size` on the book and
+    /// sends the next one whenever it is filled.
+    Iceberg { visible_size: Decimal },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExecutionState {
+    Running,
+    /// The whole size was filled
+    Completed,
+    Cancelled,
+    /// The remaining size is smaller than the smallest order the market
+    /// accepts, so no further child orders can be sent
+    BelowMinSize,
+}
+
+/// A snapshot of the execution of a `SliceExecutor`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ExecutionProgress {
+    pub state: ExecutionState,
+    pub size: Decimal,
+    pub filled_size: Decimal,
+    /// `None` before the first fill
+    pub avg_fill_price: Option<Decimal>,
+    /// The unfilled size of child orders which are open
+    pub open_size: Decimal,
+    /// The number of child orders sent so far
+    pub child_orders: usize,
+}
+
+impl ExecutionProgress {
+    pub fn remaining_size(&self) -> Decimal {
+        self.size - self.filled_size
+    }
+}
+
+/// Executes a large order client-side as a sequence of smaller limit
+/// orders, either spread over time (TWAP) or with a limited visible size
+/// (iceberg).
+///
+/// The executor is driven by market data: every ticker or orderbook update
+/// of its market passed to `update`, or view of the orderbook passed to
+/// `step`, may send the next child order, at the best price of the other side
+/// for TWAP slices and of the same side for iceberg orders, bounded by the
+/// limit price if there is one. Only one child order is open at a time, and
+/// the executor learns about its fills from the orders channel, so order
+/// updates have to be passed to `update` as well.
+///
+/// Sizes and prices of child orders are rounded according to the rules of
+/// the market.
+///
+/// ```no_run
+/// # use ftx::{options::Options, rest::{MarketRules, Rest, Side}};
+/// # use ftx::trading::{SliceExecutor, Slicing};
+/// # use ftx::ws::{Channel, Ws};
+/// # use futures::StreamExt;
+/// # use rust_decimal_macros::dec;
+/// # use std::time::Duration;
+/// # async fn example() -> ftx::trading::Result<()> {
+/// let options = Options::from_env();
+/// let rest = Rest::new(options.clone());
+/// let rules = MarketRules::fetch(&rest).await?;
+/// let mut ws = Ws::connect(options).await?;
+/// ws.subscribe(&[Channel::Ticker("BTC-PERP".to_owned()), Channel::Orders])
+///     .await?;
+///
+/// let slicing = Slicing::Twap {
+///     duration: Duration::from_secs(3600),
+///     slices: 60,
+/// };
+/// let mut executor = SliceExecutor::new(rest, rules, "BTC-PERP", Side::Buy, dec!(5), slicing)
+///     .with_limit_price(dec!(21000));
+/// while let Some((symbol, data)) = ws.next().await.transpose()? {
+///     executor.update(symbol.as_deref(), &data).await?;
+///     if executor.is_done() {
+///         break;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SliceExecutor {
+    rest: Rest,
+    market: Symbol,
+    side: Side,
+    size: Decimal,
+    slicing: Slicing,
+    limit_price: Option<Decimal>,
+    reduce_only: bool,
+    rules: MarketRules,
+    /// Built from the orderbook updates passed to `update`
+    book: Orderbook,
+    state: ExecutionState,
+    /// When the first TWAP slice was sent
+    started: Option<DateTime<Utc>>,
+    slices_sent: u32,
+    children: BTreeMap<Id, OrderInfo>,
+}
+
+impl SliceExecutor {
+    /// `rules` have to include the market, see `MarketRules::fetch`.
+    pub fn new(
+        rest: Rest,
+        rules: MarketRules,
+        market: &str,
+        side: Side,
+        size: Decimal,
+        slicing: Slicing,
+    ) -> Self {
+        Self {
+            rest,
+            market: market.to_owned(),
+            side,
+            size,
+            slicing,
+            limit_price: None,
+            reduce_only: false,
+            rules,
+            book: Orderbook::new(market.to_owned()),
+            state: ExecutionState::Running,
+            started: None,
+            slices_sent: 0,
+            children: BTreeMap::new(),
+        }
+    }
+
+    /// Never buys above or sells below `price`. Iceberg orders are placed
+    /// at this price instead of joining the best price.
+    #[must_use]
+    pub fn with_limit_price(mut self, price: Decimal) -> Self {
+        self.limit_price = Some(price);
+        self
+    }
+
+    /// Places all child orders as reduce-only
+    #[must_use]
+    pub fn with_reduce_only(mut self) -> Self {
+        self.reduce_only = true;
+        self
+    }
+
+    pub fn progress(&self) -> ExecutionProgress {
+        let filled: Decimal = self.children.values().map(filled_size).sum();
+        let notional: Decimal = self
+            .children
+            .values()
+            .filter_map(|order| Some(filled_size(order) * order.avg_fill_price?))
+            .sum();
+
+        ExecutionProgress {
+            state: self.state,
+            size: self.size,
+            filled_size: filled,
+            avg_fill_price: if filled.is_zero() {
+                None
+            } else {
+                Some(notional / filled)
+            },
+            open_size: self.open_size(),
+            child_orders: self.children.len(),
+        }
+    }
+
+    /// Whether the execution was completed, cancelled or cannot continue
+    pub fn is_done(&self) -> bool {
+        self.state != ExecutionState::Running
+    }
+
+    /// The child orders sent so far, oldest first
+    pub fn children(&self) -> impl Iterator<Item = &OrderInfo> {
+        self.children.values()
+    }
+
+    /// Applies an update of a child order, e.g. from the orders channel.
+    /// Returns whether the order is a child of this executor.
+    pub fn apply_order(&mut self, order: &OrderInfo) -> bool {
+        match self.children.get_mut(&order.id) {
+            Some(child) => {
+                *child = order.clone();
+                if self.state == ExecutionState::Running
+                    && self.progress().remaining_size() <= Decimal::ZERO
+                {
+                    self.state = ExecutionState::Completed;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies order updates, and steps with the tickers and orderbook
+    /// updates of the market of the executor received from `Ws`.
+    ///
+    /// Returns an error if the orderbook is out of sync, after which
+    /// orderbook updates are skipped until the market is subscribed to again.
+    pub async fn update(&mut self, symbol: Option<&str>, data: &Data) -> Result<()> {
+        match data {
+            Data::Order(order) => {
+                self.apply_order(order);
+            }
+            Data::Ticker(ticker) if symbol == Some(self.market.as_str()) => {
+                self.step(ticker.time, ticker).await?;
+            }
+            Data::OrderbookData(orderbook) if symbol == Some(self.market.as_str()) => {
+                // Waiting for a partial after the book was out of sync
+                if orderbook.action != OrderbookAction::Partial && !self.book.is_initialized() {
+                    return Ok(());
+                }
+                if let Err(e) = self.book.update(orderbook) {
+                    // Resubscribing sends a new partial
+                    self.book.reset();
+                    return Err(e.into());
+                }
+                let best_bid = self.book.top_bid();
+                let best_ask = self.book.top_ask();
+                self.send_next(orderbook.time, best_bid, best_ask).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Sends the next child order if one is due at `now`, priced from
+    /// `book`. Returns the order that was sent, if any.
+    pub async fn step(
+        &mut self,
+        now: DateTime<Utc>,
+        book: &impl OrderbookView,
+    ) -> Result<Option<OrderInfo>> {
+        self.send_next(now, book.top_bid(), book.top_ask()).await
+    }
+
+    async fn send_next(
+        &mut self,
+        now: DateTime<Utc>,
+        best_bid: Option<(Decimal, Decimal)>,
+        best_ask: Option<(Decimal, Decimal)>,
+    ) -> Result<Option<OrderInfo>> {
+        // Wait for the open child order to be filled or closed first
+        if self.state != ExecutionState::Running || !self.open_size().is_zero() {
+            return Ok(None);
+        }
+        let unfilled = self.progress().remaining_size();
+        if unfilled <= Decimal::ZERO {
+            return Ok(None);
+        }
+        // Partial fills can leave less than the market accepts
+        let min_size = self.min_size()?;
+        let unfilled = self.rules.round_size(&self.market, unfilled)?;
+        if unfilled < min_size {
+            self.state = ExecutionState::BelowMinSize;
+            return Ok(None);
+        }
+
+        let (price, size, ioc) = match self.slicing {
+            Slicing::Twap { duration, slices } => {
+                let slices = slices.max(1);
+                let started = *self.started.get_or_insert(now);
+                let due = due_slices(started, now, duration / slices);
+                if self.slices_sent >= due {
+                    return Ok(None);
+                }
+                let price = match self.side {
+                    Side::Buy => best_ask,
+                    Side::Sell => best_bid,
+                };
+                let price = match (price, self.limit_price) {
+                    (None, _) => return Ok(None),
+                    (Some((price, _)), None) => price,
+                    (Some((price, _)), Some(limit)) => match self.side {
+                        Side::Buy => price.min(limit),
+                        Side::Sell => price.max(limit),
+                    },
+                };
+                self.slices_sent = due;
+                // Slices that were missed are merged into this one
+                let slices_left = slices.saturating_sub(due - 1).max(1);
+                (price, unfilled / Decimal::from(slices_left), true)
+            }
+            Slicing::Iceberg { visible_size } => {
+                let best = match self.side {
+                    Side::Buy => best_bid,
+                    Side::Sell => best_ask,
+                };
+                match self.limit_price.or_else(|| best.map(|(price, _)| price)) {
+                    Some(price) => (price, visible_size.min(unfilled), false),
+                    None => return Ok(None),
+                }
+            }
+        };
+
+        // Send the minimum size instead of many orders that are too small
+        let size = self.rules.round_size(&self.market, size)?.max(min_size);
+        let price = self.rules.round_price(&self.market, price)?;
+        let order = self
+            .rest
+            .request(PlaceOrder {
+                market: &self.market,
+                side: self.side,
+                price: Some(price),
+                r#type: OrderType::Limit,
+                size,
+                reduce_only: self.reduce_only,
+                ioc,
+                ..Default::default()
+            })
+            .await?;
+        self.children.insert(order.id, order.clone());
+        Ok(Some(order))
+    }
+
+    /// Stops sending child orders and cancels the open one, if any.
+    pub async fn cancel(&mut self) -> Result<()> {
+        if self.state == ExecutionState::Running {
+            self.state = ExecutionState::Cancelled;
+        }
+        let open: Vec<Id> = self
+            .children
+            .values()
+            .filter(|order| order.status != OrderStatus::Closed)
+            .map(|order| order.id)
+            .collect();
+        for id in open {
+            match self.rest.request(CancelOrder::new(id)).await {
+                // The order was filled or cancelled in the meantime
+                Ok(_)
+                | Err(rest::Error::OrderNotFound(..))
+                | Err(rest::Error::OrderAlreadyClosed(..)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn open_size(&self) -> Decimal {
+        self.children
+            .values()
+            .filter(|order| order.status != OrderStatus::Closed)
+            .map(|order| order.size - filled_size(order))
+            .sum()
+    }
+
+    /// The smallest size of a child order in the market
+    fn min_size(&self) -> rest::Result<Decimal> {
+        match self.rules.get(&self.market) {
+            Some(rule) => Ok(rule.min_size.max(rule.size_increment)),
+            None => Err(rest::Error::UnknownMarket(self.market.clone())),
+        }
+    }
+}
+
+fn filled_size(order: &OrderInfo) -> Decimal {
+    order.filled_size.unwrap_or(Decimal::ZERO)
+}
+
+/// The number of TWAP slices due at `now`, counting the first slice at
+/// `started`
+fn due_slices(started: DateTime<Utc>, now: DateTime<Utc>, interval: Duration) -> u32 {
+    let elapsed = (now - started).to_std().unwrap_or_default();
+    if interval.is_zero() {
+        return u32::MAX;
+    }
+    u32::try_from(elapsed.as_nanos() / interval.as_nanos())
+        .unwrap_or(u32::MAX)
+        .saturating_add(1)
+}
@@ -0,0 +1,859 @@
+use super::*;
+use crate::mock::{fill, market, position, ticker, MockExchange, MockResponse, OrderBehavior};
+use crate::rest::{
+    Balance, CancelOrder, DepositStatus, GetOpenOrders, GetOrder, Id, MarketRules, OrderInfo,
+    OrderStatus, OrderType, PlaceOrder, Rest, Side, Transfer, TransferBetweenSubaccounts,
+    WalletDeposit, WalletWithdrawal, WithdrawStatus,
+};
+use crate::ws::{
+    self, Channel, Data, Orderbook, OrderbookAction, OrderbookData, ReconnectPolicy, ResponseData,
+    Type, Ws,
+};
+use chrono::Utc;
+use futures::StreamExt;
+use http::Method;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_json::json;
+use std::time::Duration;
+
+async fn init_mock() -> (MockExchange, Rest, Ws) {
+    let mock = MockExchange::start().await.unwrap();
+    let options = mock
+        .options()
+        .authenticate("key".to_owned(), "secret".to_owned());
+    let rest = Rest::new(options.clone());
+    let ws = Ws::connect(options).await.expect("Connection failed.");
+    (mock, rest, ws)
+}
+
+fn limit_order(side: Side, price: rust_decimal::Decimal) -> PlaceOrder<'static> {
+    PlaceOrder {
+        market: "BTC-PERP",
+        side,
+        price: Some(price),
+        r#type: OrderType::Limit,
+        size: dec!(0.01),
+        post_only: true,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn order_manager_lifecycle() {
+    let (mock, rest, ws) = init_mock().await;
+    let manager = OrderManager::new(rest, ws).await.unwrap();
+
+    // Accepted, partially filled, then cancelled
+    let mut handle = manager
+        .place(limit_order(Side::Buy, dec!(19000)))
+        .await
+        .unwrap();
+    assert_eq!(OrderState::Accepted, handle.outcome().await.unwrap());
+
+    let mut order = mock.orders().pop().unwrap();
+    assert_eq!(Some(handle.client_id()), order.client_id.as_deref());
+    order.status = crate::rest::OrderStatus::New;
+    order.filled_size = Some(dec!(0.004));
+    mock.publish(&Channel::Orders, Type::Update, &ResponseData::Order(order));
+    assert_eq!(
+        OrderState::PartiallyFilled,
+        handle
+            .wait_for(|state| state != OrderState::Accepted)
+            .await
+            .unwrap()
+    );
+    assert_eq!(dec!(0.004), handle.order().filled_size.unwrap());
+
+    manager.cancel(&handle).await.unwrap();
+    assert_eq!(OrderState::Cancelled, handle.closed().await.unwrap());
+
+    // Rejected during processing
+    mock.push_order_behavior(OrderBehavior::Reject);
+    let mut handle = manager
+        .place(limit_order(Side::Buy, dec!(21000)))
+        .await
+        .unwrap();
+    assert_eq!(OrderState::Rejected, handle.outcome().await.unwrap());
+
+    // Filled immediately
+    mock.push_order_behavior(OrderBehavior::Fill);
+    let mut handle = manager
+        .place(limit_order(Side::Sell, dec!(19000)))
+        .await
+        .unwrap();
+    assert_eq!(OrderState::Filled, handle.closed().await.unwrap());
+    assert_eq!(OrderState::Filled, manager.refresh(&handle).await.unwrap());
+}
+
+#[tokio::test]
+async fn order_manager_client_ids() {
+    let (mock, rest, ws) = init_mock().await;
+    mock.add_market(market("BTC-PERP", dec!(1), dec!(0.0001)));
+    let manager = OrderManager::new(rest, ws).await.unwrap();
+
+    let first = manager
+        .place(limit_order(Side::Buy, dec!(19000)))
+        .await
+        .unwrap();
+    let second = manager
+        .place(limit_order(Side::Buy, dec!(19000)))
+        .await
+        .unwrap();
+    assert_ne!(first.client_id(), second.client_id());
+
+    let mut named = manager
+        .place(PlaceOrder {
+            client_id: Some("named"),
+            ..limit_order(Side::Buy, dec!(19000))
+        })
+        .await
+        .unwrap();
+    assert_eq!("named", named.client_id());
+
+    let duplicate = manager
+        .place(PlaceOrder {
+            client_id: Some("named"),
+            ..limit_order(Side::Buy, dec!(19000))
+        })
+        .await;
+    assert!(matches!(duplicate, Err(Error::DuplicateClientId(_))));
+
+    // Orders that could not be placed are not tracked
+    let unknown_market = manager
+        .place(PlaceOrder {
+            market: "ETH-PERP",
+            ..limit_order(Side::Buy, dec!(19000))
+        })
+        .await;
+    assert!(matches!(unknown_market, Err(Error::Rest(_))));
+    assert_eq!(OrderState::Accepted, named.outcome().await.unwrap());
+}
+
+#[tokio::test]
+async fn order_manager_stopped() {
+    let (mock, rest, ws) = init_mock().await;
+    let manager = OrderManager::new(rest, ws).await.unwrap();
+
+    let mut handle = manager
+        .place(limit_order(Side::Buy, dec!(19000)))
+        .await
+        .unwrap();
+    handle.outcome().await.unwrap();
+
+    mock.disconnect_all();
+    assert!(matches!(handle.closed().await, Err(Error::Stopped)));
+    assert!(!manager.is_running());
+    assert!(matches!(
+        manager.place(limit_order(Side::Buy, dec!(19000))).await,
+        Err(Error::Stopped)
+    ));
+}
+
+#[tokio::test]
+async fn cancel_on_disconnect_connection_lost() {
+    let (mock, rest, ws) = init_mock().await;
+    let mut guard = CancelOnDisconnect::new(rest.clone(), ws)
+        .unwrap()
+        .with_scope(CancelScope {
+            market: Some("BTC-PERP".to_owned()),
+            ..Default::default()
+        });
+
+    let order = rest
+        .request(limit_order(Side::Buy, dec!(19000)))
+        .await
+        .unwrap();
+    rest.request(PlaceOrder {
+        market: "ETH-PERP",
+        ..limit_order(Side::Buy, dec!(1000))
+    })
+    .await
+    .unwrap();
+
+    mock.disconnect_all();
+    match guard.next().await.unwrap() {
+        Ok(GuardEvent::Cancelled(report)) => {
+            assert_eq!(CancelReason::ConnectionLost, report.reason);
+            assert_eq!(vec![order.id], ids(&report.orders));
+        }
+        other => panic!("Cancellation expected, got {:?}", other),
+    }
+    assert!(matches!(
+        guard.next().await.unwrap(),
+        Err(Error::Ws(ws::Error::ConnectionClosed))
+    ));
+    assert!(!guard.is_armed());
+
+    let open: Vec<_> = mock
+        .orders()
+        .into_iter()
+        .filter(|order| order.status != OrderStatus::Closed)
+        .collect();
+    assert_eq!(1, open.len());
+    assert_eq!("ETH-PERP", open[0].market);
+}
+
+#[tokio::test]
+async fn cancel_on_disconnect_missed_pongs() {
+    let (mock, rest, ws) = init_mock().await;
+    let ws = ws
+        .with_ping_interval(Duration::from_millis(20))
+        .with_max_missed_pongs(2);
+    let mut guard = CancelOnDisconnect::new(rest.clone(), ws).unwrap();
+    let order = rest
+        .request(limit_order(Side::Buy, dec!(19000)))
+        .await
+        .unwrap();
+
+    mock.set_ignore_pings(true);
+    match guard.next().await.unwrap() {
+        Ok(GuardEvent::Cancelled(report)) => {
+            assert_eq!(CancelReason::MissedPongs, report.reason);
+            assert_eq!(vec![order.id], ids(&report.orders));
+        }
+        other => panic!("Cancellation expected, got {:?}", other),
+    }
+    assert!(matches!(
+        guard.next().await.unwrap(),
+        Err(Error::Ws(ws::Error::MissedPongs(2)))
+    ));
+}
+
+#[tokio::test]
+async fn cancel_on_disconnect_missed_pongs_reconnect() {
+    let (mock, rest, ws) = init_mock().await;
+    let ws = ws
+        .with_ping_interval(Duration::from_millis(20))
+        .with_max_missed_pongs(2)
+        .with_reconnect(ReconnectPolicy {
+            max_attempts: Some(3),
+            delay: Duration::from_millis(10),
+        });
+    let mut guard = CancelOnDisconnect::new(rest.clone(), ws).unwrap();
+    rest.request(limit_order(Side::Buy, dec!(19000)))
+        .await
+        .unwrap();
+
+    mock.set_ignore_pings(true);
+    assert!(matches!(
+        guard.next().await.unwrap(),
+        Ok(GuardEvent::Data(None, Data::Disconnected))
+    ));
+    match guard.next().await.unwrap() {
+        Ok(GuardEvent::Cancelled(report)) => {
+            assert_eq!(CancelReason::MissedPongs, report.reason);
+            assert_eq!(1, report.orders.len());
+        }
+        other => panic!("Cancellation expected, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn cancel_on_disconnect_reconnect_and_shutdown() {
+    let (mock, rest, ws) = init_mock().await;
+    let ws = ws.with_reconnect(ReconnectPolicy {
+        max_attempts: Some(3),
+        delay: Duration::from_millis(10),
+    });
+    let mut guard = CancelOnDisconnect::new(rest.clone(), ws).unwrap();
+    guard.ws().subscribe(&[Channel::Orders]).await.unwrap();
+
+    rest.request(limit_order(Side::Buy, dec!(19000)))
+        .await
+        .unwrap();
+    // The order update
+    guard.next().await.unwrap().unwrap();
+
+    mock.disconnect_all();
+    assert!(matches!(
+        guard.next().await.unwrap(),
+        Ok(GuardEvent::Data(None, Data::Disconnected))
+    ));
+    match guard.next().await.unwrap() {
+        Ok(GuardEvent::Cancelled(report)) => {
+            assert_eq!(CancelReason::ConnectionLost, report.reason);
+            assert_eq!(1, report.orders.len());
+        }
+        other => panic!("Cancellation expected, got {:?}", other),
+    }
+    // Disarmed until reconnected
+    assert!(!guard.is_armed());
+    assert!(matches!(
+        guard.next().await.unwrap(),
+        Ok(GuardEvent::Data(None, Data::Reconnected))
+    ));
+    assert!(guard.is_armed());
+
+    let order = rest
+        .request(limit_order(Side::Sell, dec!(21000)))
+        .await
+        .unwrap();
+    let report = guard.shutdown().await.unwrap();
+    assert_eq!(CancelReason::Shutdown, report.reason);
+    assert_eq!(vec![order.id], ids(&report.orders));
+    assert!(mock
+        .orders()
+        .iter()
+        .all(|order| order.status == OrderStatus::Closed));
+}
+
+#[tokio::test]
+async fn cancel_on_disconnect_requires_authentication() {
+    let mock = MockExchange::start().await.unwrap();
+    let ws = Ws::connect(mock.options()).await.unwrap();
+    assert!(matches!(
+        CancelOnDisconnect::new(Rest::new(mock.options()), ws),
+        Err(Error::Ws(ws::Error::SocketNotAuthenticated))
+    ));
+}
+
+fn ids(orders: &[OrderInfo]) -> Vec<Id> {
+    orders.iter().map(|order| order.id).collect()
+}
+
+#[test]
+fn position_tracker_accounting() {
+    let mut tracker = PositionTracker::new();
+    let now = Utc::now();
+    let mut buy = fill(1, "BTC-PERP", Side::Buy, dec!(100), dec!(1), now);
+    buy.fee = dec!(0.07);
+
+    assert!(tracker.apply_fill(&buy));
+    // Fills are only applied once
+    assert!(!tracker.apply_fill(&buy));
+    tracker.apply_fill(&fill(2, "BTC-PERP", Side::Buy, dec!(110), dec!(1), now));
+    let position = tracker.position("BTC-PERP").unwrap();
+    assert_eq!(dec!(2), position.net_size);
+    assert_eq!(Some(dec!(105)), position.entry_price);
+    assert_eq!(None, position.unrealized_pnl());
+
+    tracker.mark_ticker("BTC-PERP", &ticker(dec!(119), dec!(121), dec!(120)));
+    assert_eq!(Some(dec!(30)), tracker.unrealized_pnl());
+
+    // Flipping from long to short realizes the long position
+    tracker.apply_fill(&fill(3, "BTC-PERP", Side::Sell, dec!(120), dec!(3), now));
+    let position = tracker.position("BTC-PERP").unwrap();
+    assert_eq!(Some(Side::Sell), position.side());
+    assert_eq!(dec!(-1), position.net_size);
+    assert_eq!(Some(dec!(120)), position.entry_price);
+    assert_eq!(dec!(30), tracker.realized_pnl());
+
+    let mut orderbook = Orderbook::new("BTC-PERP".to_owned());
+    orderbook.bids.insert(dec!(114), dec!(1));
+    orderbook.asks.insert(dec!(116), dec!(1));
+    tracker.mark_orderbook(&orderbook);
+    assert_eq!(Some(dec!(5)), tracker.unrealized_pnl());
+
+    tracker.apply_fill(&fill(4, "BTC-PERP", Side::Buy, dec!(115), dec!(1), now));
+    let position = tracker.position("BTC-PERP").unwrap();
+    assert_eq!(None, position.side());
+    assert_eq!(None, position.entry_price);
+    assert_eq!(dec!(35), tracker.realized_pnl());
+    assert_eq!(Some(&dec!(0.07)), tracker.fees().get("USD"));
+}
+
+#[test]
+fn position_tracker_seed_and_fill_ids() {
+    let mut tracker = PositionTracker::new();
+    let mut short = position("ETH-PERP", dec!(-2), dec!(1000));
+    short.entry_price = None;
+    let mut unknown = position("BTC-PERP", dec!(1), dec!(20000));
+    unknown.entry_price = None;
+    unknown.cost = Decimal::ZERO;
+    tracker.seed(&[short, unknown]);
+
+    // The entry price is derived from the cost, or the position is skipped
+    let position = tracker.position("ETH-PERP").unwrap();
+    assert_eq!(dec!(-2), position.net_size);
+    assert_eq!(Some(dec!(1000)), position.entry_price);
+    assert!(tracker.position("BTC-PERP").is_none());
+
+    // Only the latest fills are remembered
+    let now = Utc::now();
+    let first = fill(0, "SOL-PERP", Side::Buy, dec!(10), dec!(1), now);
+    assert!(tracker.apply_fill(&first));
+    for id in 1..=10_000 {
+        tracker.apply_fill(&fill(id, "SOL-PERP", Side::Buy, dec!(10), dec!(1), now));
+    }
+    assert!(!tracker.apply_fill(&fill(1, "SOL-PERP", Side::Buy, dec!(10), dec!(1), now)));
+    assert!(tracker.apply_fill(&first));
+}
+
+#[tokio::test]
+async fn position_tracker_seeded_from_rest() {
+    let (mock, rest, mut ws) = init_mock().await;
+    mock.set_positions(vec![position("ETH-PERP", dec!(-2), dec!(1000))]);
+
+    let mut tracker = PositionTracker::fetch(&rest).await.unwrap();
+    ws.subscribe(&[Channel::Fills, Channel::Ticker("ETH-PERP".to_owned())])
+        .await
+        .unwrap();
+
+    mock.publish_fill(fill(
+        1,
+        "ETH-PERP",
+        Side::Buy,
+        dec!(900),
+        dec!(1),
+        Utc::now(),
+    ));
+    mock.publish_ticker("ETH-PERP", ticker(dec!(949), dec!(951), dec!(950)));
+    for _ in 0..2 {
+        let (symbol, data) = ws.next().await.unwrap().unwrap();
+        tracker.update(symbol.as_deref(), &data);
+    }
+
+    let position = tracker.position("ETH-PERP").unwrap();
+    assert_eq!(dec!(-1), position.net_size);
+    assert_eq!(Some(dec!(1000)), position.entry_price);
+    assert_eq!(dec!(100), position.realized_pnl);
+    assert_eq!(Some(dec!(50)), position.unrealized_pnl());
+}
+
+#[test]
+fn balance_tracker_deltas() {
+    let mut tracker = BalanceTracker::new(Some("bot")).with_tolerance(dec!(0.01));
+    tracker.seed_subaccount(&[
+        Balance {
+            coin: "USD".to_owned(),
+            free: dec!(1000),
+            total: dec!(1000),
+            spot_borrow: dec!(0),
+            available_without_borrow: dec!(1000),
+        },
+        Balance {
+            coin: "BTC".to_owned(),
+            free: dec!(1),
+            total: dec!(1),
+            spot_borrow: dec!(0),
+            available_without_borrow: dec!(1),
+        },
+    ]);
+
+    let mut buy = fill(1, "BTC/USD", Side::Buy, dec!(100), dec!(2), Utc::now());
+    buy.fee = dec!(0.2);
+    assert!(tracker.apply_fill(&buy));
+    assert!(!tracker.apply_fill(&buy));
+    // Futures fills only pay fees
+    let mut perp = fill(2, "BTC-PERP", Side::Sell, dec!(100), dec!(5), Utc::now());
+    perp.fee = dec!(0.5);
+    tracker.apply_fill(&perp);
+    assert_eq!(dec!(3), tracker.balance("BTC"));
+    assert_eq!(dec!(799.3), tracker.balance("USD"));
+
+    let transfer = |id, size| Transfer {
+        id,
+        coin: "USD".to_owned(),
+        size,
+        time: Utc::now(),
+        notes: String::new(),
+    };
+    let to_main = TransferBetweenSubaccounts::new("USD", dec!(100), "bot", "main");
+    assert!(tracker.apply_transfer(&to_main, &transfer(1, dec!(100))));
+    let from_main = TransferBetweenSubaccounts::new("USD", dec!(50), "main", "bot");
+    assert!(tracker.apply_transfer(&from_main, &transfer(2, dec!(50))));
+    let unrelated = TransferBetweenSubaccounts::new("USD", dec!(50), "main", "other");
+    assert!(!tracker.apply_transfer(&unrelated, &transfer(3, dec!(50))));
+    assert_eq!(dec!(749.3), tracker.balance("USD"));
+
+    let deposit = |id, status| WalletDeposit {
+        id,
+        coin: "ETH".to_owned(),
+        size: Some(dec!(10)),
+        time: Utc::now().to_rfc3339(),
+        status,
+        confirmations: None,
+        confirmed_time: None,
+        fee: None,
+        txid: None,
+        notes: None,
+    };
+    assert!(!tracker.apply_deposit(&deposit(1, DepositStatus::Unconfirmed)));
+    assert!(tracker.apply_deposit(&deposit(1, DepositStatus::Confirmed)));
+    assert!(!tracker.apply_deposit(&deposit(1, DepositStatus::Complete)));
+    tracker.apply_withdrawal(&WalletWithdrawal {
+        coin: "ETH".to_owned(),
+        size: dec!(4),
+        time: Utc::now().to_rfc3339(),
+        address: Some("0x0".to_owned()),
+        status: WithdrawStatus::Requested,
+        fee: Some(dec!(0.01)),
+        txid: None,
+        tag: None,
+        notes: None,
+    });
+    assert_eq!(dec!(5.99), tracker.balance("ETH"));
+}
+
+#[tokio::test]
+async fn balance_tracker_reconcile() {
+    let (mock, rest, _) = init_mock().await;
+    let balance = |coin: &str, total: Decimal| {
+        json!({
+            "coin": coin,
+            "free": total,
+            "total": total,
+            "usdValue": null,
+            "spotBorrow": 0,
+            "availableWithoutBorrow": total,
+        })
+    };
+    mock.respond_once(
+        Method::GET,
+        "/wallet/balances",
+        MockResponse::success(json!([balance("USD", dec!(1000)), balance("BTC", dec!(1))])),
+    );
+    let mut tracker = BalanceTracker::fetch(&rest)
+        .await
+        .unwrap()
+        .with_tolerance(dec!(0.01));
+
+    tracker.apply_fill(&fill(
+        1,
+        "BTC/USD",
+        Side::Sell,
+        dec!(100),
+        dec!(1),
+        Utc::now(),
+    ));
+    assert_eq!(dec!(1100), tracker.balance("USD"));
+
+    // Funding payments are only picked up by reconciling
+    mock.respond_once(
+        Method::GET,
+        "/wallet/balances",
+        MockResponse::success(json!([
+            balance("USD", dec!(1099.995)),
+            balance("ETH", dec!(2)),
+        ])),
+    );
+    let drifts = tracker.reconcile_with(&rest).await.unwrap();
+    assert_eq!(
+        vec![BalanceDrift {
+            coin: "ETH".to_owned(),
+            tracked: dec!(0),
+            actual: dec!(2),
+        }],
+        drifts
+    );
+    assert_eq!(dec!(1099.995), tracker.balance("USD"));
+    assert_eq!(dec!(0), tracker.balance("BTC"));
+}
+
+fn prices(orders: &[OrderInfo]) -> Vec<(Side, Decimal, Decimal)> {
+    let mut prices: Vec<_> = orders
+        .iter()
+        .map(|order| (order.side, order.price.unwrap(), order.size))
+        .collect();
+    prices.sort_by_key(|(_, price, _)| *price);
+    prices
+}
+
+#[tokio::test]
+async fn quote_ladder() {
+    let (mock, rest, _ws) = init_mock().await;
+    mock.add_market(market("BTC-PERP", dec!(1), dec!(0.01)));
+    let mut ladder = QuoteLadder::new(rest.clone()).with_post_only(true);
+
+    let report = ladder
+        .apply(
+            "BTC-PERP",
+            &[
+                Quote::new(Side::Buy, dec!(99), dec!(1)),
+                Quote::new(Side::Buy, dec!(98), dec!(1)),
+                Quote::new(Side::Sell, dec!(101), dec!(1)),
+                Quote::new(Side::Sell, dec!(102), dec!(1)),
+            ],
+        )
+        .await;
+    assert!(report.is_complete());
+    assert_eq!(4, report.placed.len());
+    let bid_98 = report.placed[1].id;
+
+    // Unchanged quotes are kept, the others are modified or cancelled
+    let quotes = [
+        Quote::new(Side::Buy, dec!(99), dec!(1)),
+        Quote::new(Side::Buy, dec!(97), dec!(2)),
+        Quote::new(Side::Sell, dec!(101), dec!(1)),
+    ];
+    let requests = mock.requests().len();
+    let report = ladder.apply("BTC-PERP", &quotes).await;
+    assert_eq!(2, mock.requests().len() - requests);
+    assert!(report.placed.is_empty());
+    assert_eq!(1, report.modified.len());
+    let (id, modified) = &report.modified[0];
+    assert_eq!(bid_98, *id);
+    assert_eq!(Some(dec!(97)), modified.price);
+    assert_eq!(1, report.cancelled.len());
+
+    let open_orders = rest.request(GetOpenOrders::all_market()).await.unwrap();
+    let expected = vec![
+        (Side::Buy, dec!(97), dec!(2)),
+        (Side::Buy, dec!(99), dec!(1)),
+        (Side::Sell, dec!(101), dec!(1)),
+    ];
+    assert_eq!(expected, prices(&open_orders));
+    let tracked: Vec<_> = ladder.orders("BTC-PERP").cloned().collect();
+    assert_eq!(expected, prices(&tracked));
+    assert!(ladder.diff("BTC-PERP", &quotes).is_empty());
+
+    // An order cancelled elsewhere can not be modified and is forgotten
+    rest.request(CancelOrder::new(modified.id)).await.unwrap();
+    let report = ladder
+        .apply(
+            "BTC-PERP",
+            &[
+                Quote::new(Side::Buy, dec!(99), dec!(1)),
+                Quote::new(Side::Buy, dec!(96), dec!(2)),
+                Quote::new(Side::Sell, dec!(101), dec!(1)),
+            ],
+        )
+        .await;
+    assert_eq!(1, report.failed.len());
+    assert_eq!(2, ladder.orders("BTC-PERP").count());
+
+    // Orders closed according to the orders channel are forgotten as well
+    let bid_99 = tracked
+        .iter()
+        .find(|order| order.price == Some(dec!(99)))
+        .unwrap();
+    let mut closed = rest.request(GetOrder::new(bid_99.id)).await.unwrap();
+    closed.status = OrderStatus::Closed;
+    ladder.update(&Data::Order(closed));
+    assert_eq!(
+        vec![
+            QuoteAction::Place(Quote::new(Side::Buy, dec!(99), dec!(1))),
+            QuoteAction::Place(Quote::new(Side::Buy, dec!(96), dec!(2))),
+        ],
+        ladder.diff(
+            "BTC-PERP",
+            &[
+                Quote::new(Side::Buy, dec!(99), dec!(1)),
+                Quote::new(Side::Buy, dec!(96), dec!(2)),
+                Quote::new(Side::Sell, dec!(101), dec!(1)),
+            ],
+        )
+    );
+}
+
+#[tokio::test]
+async fn slice_executor_twap() {
+    let (mock, rest, _ws) = init_mock().await;
+    let btc_perp = market("BTC-PERP", dec!(1), dec!(0.01));
+    mock.add_market(btc_perp.clone());
+    let mut rules = MarketRules::new();
+    rules.insert_market(&btc_perp);
+    mock.set_order_behavior(OrderBehavior::Fill);
+    let child = |id: Id| {
+        mock.orders()
+            .into_iter()
+            .find(|order| order.id == id)
+            .unwrap()
+    };
+
+    let slicing = Slicing::Twap {
+        duration: Duration::from_secs(60),
+        slices: 2,
+    };
+    let mut executor = SliceExecutor::new(rest, rules, "BTC-PERP", Side::Buy, dec!(4), slicing)
+        .with_limit_price(dec!(101.2));
+    let t0 = Utc::now();
+
+    // The first slice is sent right away at the best ask
+    let mut data = ticker(dec!(99), dec!(100), dec!(100));
+    data.time = t0;
+    executor
+        .update(Some("BTC-PERP"), &Data::Ticker(data))
+        .await
+        .unwrap();
+    let first = executor.children().next().unwrap().clone();
+    assert_eq!(Some(dec!(100)), first.price);
+    assert_eq!(dec!(2), first.size);
+    assert_eq!(Some(true), first.ioc);
+    assert_eq!(dec!(2), executor.progress().open_size);
+    executor
+        .update(None, &Data::Order(child(first.id)))
+        .await
+        .unwrap();
+    let progress = executor.progress();
+    assert_eq!(dec!(2), progress.filled_size);
+    assert_eq!(Some(dec!(100)), progress.avg_fill_price);
+
+    // The next slice is not due yet
+    let book = ticker(dec!(101), dec!(102), dec!(102));
+    let step = executor.step(t0 + chrono::Duration::seconds(20), &book);
+    assert!(step.await.unwrap().is_none());
+
+    // A slice that is not filled is sent again after the last interval,
+    // never above the limit price, which is rounded to the price increment
+    mock.push_order_behavior(OrderBehavior::Reject);
+    let second = executor
+        .step(t0 + chrono::Duration::seconds(30), &book)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(Some(dec!(101)), second.price);
+    assert_eq!(dec!(2), second.size);
+    executor.apply_order(&child(second.id));
+    assert_eq!(dec!(2), executor.progress().remaining_size());
+
+    let third = executor
+        .step(t0 + chrono::Duration::seconds(60), &book)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(dec!(2), third.size);
+    executor.apply_order(&child(third.id));
+    let progress = executor.progress();
+    assert_eq!(ExecutionState::Completed, progress.state);
+    assert_eq!(dec!(4), progress.filled_size);
+    assert_eq!(Some(dec!(100.5)), progress.avg_fill_price);
+    assert_eq!(3, progress.child_orders);
+    assert!(executor.is_done());
+}
+
+#[tokio::test]
+async fn slice_executor_iceberg() {
+    let (mock, rest, _ws) = init_mock().await;
+    let btc_perp = market("BTC-PERP", dec!(1), dec!(0.5));
+    mock.add_market(btc_perp.clone());
+    let mut rules = MarketRules::new();
+    rules.insert_market(&btc_perp);
+    let child = |id: Id| {
+        mock.orders()
+            .into_iter()
+            .find(|order| order.id == id)
+            .unwrap()
+    };
+
+    let slicing = Slicing::Iceberg {
+        visible_size: dec!(1.1),
+    };
+    let mut executor = SliceExecutor::new(rest, rules, "BTC-PERP", Side::Sell, dec!(3), slicing);
+    let book = ticker(dec!(99), dec!(100), dec!(100));
+
+    // Joins the best ask with the visible size rounded down
+    let first = executor.step(Utc::now(), &book).await.unwrap().unwrap();
+    assert_eq!(Some(dec!(100)), first.price);
+    assert_eq!(dec!(1), first.size);
+    assert_eq!(Some(false), first.ioc);
+
+    // Nothing is sent while the visible order is open
+    let mut partial = child(first.id);
+    partial.filled_size = Some(dec!(0.4));
+    executor.apply_order(&partial);
+    assert!(executor.step(Utc::now(), &book).await.unwrap().is_none());
+    assert_eq!(dec!(0.6), executor.progress().open_size);
+
+    let mut filled = partial;
+    filled.status = OrderStatus::Closed;
+    filled.filled_size = Some(dec!(1));
+    filled.avg_fill_price = Some(dec!(100));
+    executor.apply_order(&filled);
+    let second = executor.step(Utc::now(), &book).await.unwrap().unwrap();
+    assert_eq!(dec!(1), second.size);
+
+    // Cancelling closes the visible order
+    executor.cancel().await.unwrap();
+    executor.apply_order(&child(second.id));
+    let progress = executor.progress();
+    assert_eq!(ExecutionState::Cancelled, progress.state);
+    assert_eq!(dec!(1), progress.filled_size);
+    assert_eq!(dec!(2), progress.remaining_size());
+    assert!(progress.open_size.is_zero());
+    assert!(executor.step(Utc::now(), &book).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn slice_executor_orderbook() {
+    let (mock, rest, _ws) = init_mock().await;
+    let btc_perp = market("BTC-PERP", dec!(1), dec!(0.5));
+    mock.add_market(btc_perp.clone());
+    mock.set_order_behavior(OrderBehavior::Fill);
+    let mut rules = MarketRules::new();
+    rules.insert_market(&btc_perp);
+
+    let slicing = Slicing::Twap {
+        duration: Duration::from_secs(60),
+        slices: 1,
+    };
+    let mut executor = SliceExecutor::new(rest, rules, "BTC-PERP", Side::Buy, dec!(1.2), slicing);
+    let symbol = Some("BTC-PERP");
+
+    // Orderbook updates of other markets are ignored
+    let snapshot = crate::rest::Orderbook {
+        bids: vec![(dec!(99), dec!(1))],
+        asks: vec![(dec!(100), dec!(1))],
+    };
+    let partial = Data::OrderbookData(OrderbookData::from_snapshot(&snapshot));
+    executor.update(Some("ETH-PERP"), &partial).await.unwrap();
+    assert_eq!(0, executor.progress().child_orders);
+
+    // The size is rounded down to the size increment
+    executor.update(symbol, &partial).await.unwrap();
+    let child = executor.children().next().unwrap().clone();
+    assert_eq!(Some(dec!(100)), child.price);
+    assert_eq!(dec!(1), child.size);
+    let filled = mock.orders().into_iter().find(|o| o.id == child.id);
+    executor
+        .update(None, &Data::Order(filled.unwrap()))
+        .await
+        .unwrap();
+
+    // The remaining 0.2 cannot be placed
+    executor.update(symbol, &partial).await.unwrap();
+    let progress = executor.progress();
+    assert_eq!(ExecutionState::BelowMinSize, progress.state);
+    assert_eq!(dec!(0.2), progress.remaining_size());
+    assert_eq!(1, progress.child_orders);
+    assert!(executor.is_done());
+}
+
+#[tokio::test]
+async fn slice_executor_orderbook_out_of_sync() {
+    let (mock, rest, _ws) = init_mock().await;
+    let btc_perp = market("BTC-PERP", dec!(1), dec!(0.5));
+    mock.add_market(btc_perp.clone());
+    let mut rules = MarketRules::new();
+    rules.insert_market(&btc_perp);
+
+    let slicing = Slicing::Iceberg {
+        visible_size: dec!(1),
+    };
+    let mut executor = SliceExecutor::new(rest, rules, "BTC-PERP", Side::Buy, dec!(2), slicing);
+    let symbol = Some("BTC-PERP");
+
+    // Without bids, there is no price to join
+    let asks = crate::rest::Orderbook {
+        bids: vec![],
+        asks: vec![(dec!(100), dec!(1))],
+    };
+    let partial = OrderbookData::from_snapshot(&asks);
+    executor
+        .update(symbol, &Data::OrderbookData(partial.clone()))
+        .await
+        .unwrap();
+
+    // A checksum mismatch is reported once, after which updates are skipped
+    let bad_update = OrderbookData {
+        action: OrderbookAction::Update,
+        bids: vec![(dec!(99), dec!(1))],
+        checksum: 0,
+        ..partial
+    };
+    let bad_update = Data::OrderbookData(bad_update);
+    assert!(executor.update(symbol, &bad_update).await.is_err());
+    executor.update(symbol, &bad_update).await.unwrap();
+    assert_eq!(0, executor.progress().child_orders);
+
+    // A new partial resumes the execution
+    let book = crate::rest::Orderbook {
+        bids: vec![(dec!(99), dec!(1))],
+        asks: vec![(dec!(100), dec!(1))],
+    };
+    let partial = Data::OrderbookData(OrderbookData::from_snapshot(&book));
+    executor.update(symbol, &partial).await.unwrap();
+    let child = executor.children().next().unwrap();
+    assert_eq!(Some(dec!(99)), child.price);
+}
@@ -0,0 +1,271 @@
+use super::{Data, Side, Symbol, Trade};
+use crate::rest::{self, Candle, GetTrades, Resolution, Rest};
+use chrono::{DateTime, Duration, Utc};
+use futures::TryStreamExt;
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+
+/// A candle built from trades by a `CandleBuilder`, along with the
+/// statistics enabled on the builder.
+///
+/// Like the candles of FTX, the volume is the traded notional in the quote
+/// currency. Statistics that are not enabled are `None`.
+#[derive(Copy, Clone, Debug)]
+pub struct LiveCandle {
+    pub candle: Candle,
+    /// The number of trades within the candle
+    pub trades: u64,
+    /// The volume-weighted average price, `None` without trades
+    pub vwap: Option<Decimal>,
+    /// The notional of trades where the taker bought
+    pub buy_volume: Option<Decimal>,
+    /// The notional of trades where the taker sold
+    pub sell_volume: Option<Decimal>,
+    /// The notional of liquidation trades
+    pub liquidation_volume: Option<Decimal>,
+}
+
+impl From<LiveCandle> for Candle {
+    fn from(candle: LiveCandle) -> Self {
+        candle.candle
+    }
+}
+
+/// The candle that is currently being built
+#[derive(Clone, Debug)]
+struct CurrentCandle {
+    candle: Candle,
+    trades: u64,
+    size: Decimal,
+    buy_volume: Decimal,
+    sell_volume: Decimal,
+    liquidation_volume: Decimal,
+    /// The times of the earliest and latest trade, so that trades which
+    /// arrive out of order still set the right open and close
+    first_trade: Option<DateTime<Utc>>,
+    last_trade: Option<DateTime<Utc>>,
+    /// The trades applied to this candle, so that trades received both over
+    /// websockets and REST are only counted once
+    applied: HashSet<rest::Id>,
+}
+
+impl CurrentCandle {
+    /// An empty candle at the given price, for intervals without trades
+    fn flat(start_time: DateTime<Utc>, price: Decimal) -> Self {
+        Self {
+            candle: Candle {
+                close: price,
+                high: price,
+                low: price,
+                open: price,
+                volume: Decimal::ZERO,
+                start_time,
+            },
+            trades: 0,
+            size: Decimal::ZERO,
+            buy_volume: Decimal::ZERO,
+            sell_volume: Decimal::ZERO,
+            liquidation_volume: Decimal::ZERO,
+            first_trade: None,
+            last_trade: None,
+            applied: HashSet::new(),
+        }
+    }
+
+    fn apply(&mut self, trade: &Trade) {
+        if !self.applied.insert(trade.id) {
+            return;
+        }
+
+        let candle = &mut self.candle;
+        if !matches!(self.first_trade, Some(first) if first <= trade.time) {
+            self.first_trade = Some(trade.time);
+            candle.open = trade.price;
+        }
+        if !matches!(self.last_trade, Some(last) if trade.time < last) {
+            self.last_trade = Some(trade.time);
+            candle.close = trade.price;
+        }
+        if self.trades == 0 {
+            candle.high = trade.price;
+            candle.low = trade.price;
+        } else {
+            candle.high = candle.high.max(trade.price);
+            candle.low = candle.low.min(trade.price);
+        }
+
+        let notional = trade.price * trade.size;
+        candle.volume += notional;
+        self.size += trade.size;
+        self.trades += 1;
+        match trade.side {
+            Side::Buy => self.buy_volume += notional,
+            Side::Sell => self.sell_volume += notional,
+        }
+        if trade.liquidation {
+            self.liquidation_volume += notional;
+        }
+    }
+}
+
+/// Builds candles of any `Resolution` from the trades of a single market,
+/// e.g. as received from the trades channel.
+///
+/// A candle is completed once a trade of a later interval arrives, or once
+/// its interval has passed according to `flush`. Intervals without trades
+/// are completed as candles without volume at the previous close, like FTX
+/// returns them. Trades of already completed candles are ignored.
+///
+/// ```no_run
+/// # use ftx::{options::Options, rest::{Resolution, Rest}, ws::{CandleBuilder, Channel, Ws}};
+/// # use futures::StreamExt;
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut ws = Ws::connect(Options::default()).await?;
+/// ws.subscribe(&[Channel::Trades("BTC-PERP".to_owned())]).await?;
+///
+/// let mut builder = CandleBuilder::new("BTC-PERP".to_owned(), Resolution::Minute).with_vwap();
+/// // Start with the trades of the current minute
+/// builder.backfill(&Rest::new(Options::default())).await?;
+///
+/// while let Some((symbol, data)) = ws.next().await.transpose()? {
+///     for candle in builder.update(symbol.as_deref(), &data) {
+///         println!("{:?}", candle);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CandleBuilder {
+    pub symbol: Symbol,
+    interval: Duration,
+    vwap: bool,
+    side_volume: bool,
+    liquidation_volume: bool,
+    current: Option<CurrentCandle>,
+}
+
+impl CandleBuilder {
+    pub fn new(symbol: Symbol, resolution: Resolution) -> Self {
+        Self {
+            symbol,
+            interval: Duration::seconds(i64::from(resolution.get_seconds())),
+            vwap: false,
+            side_volume: false,
+            liquidation_volume: false,
+            current: None,
+        }
+    }
+
+    /// Computes the volume-weighted average price of every candle
+    #[must_use]
+    pub fn with_vwap(mut self) -> Self {
+        self.vwap = true;
+        self
+    }
+
+    /// Splits the volume of every candle into buy and sell volume
+    #[must_use]
+    pub fn with_side_volume(mut self) -> Self {
+        self.side_volume = true;
+        self
+    }
+
+    /// Computes the volume of liquidations within every candle
+    #[must_use]
+    pub fn with_liquidation_volume(mut self) -> Self {
+        self.liquidation_volume = true;
+        self
+    }
+
+    /// Applies a trade and returns the candles it completed, oldest first.
+    pub fn apply_trade(&mut self, trade: &Trade) -> Vec<LiveCandle> {
+        let completed = self.advance(trade.time);
+        let start_time = rest::align(trade.time, self.interval.num_seconds());
+        let current = self
+            .current
+            .get_or_insert_with(|| CurrentCandle::flat(start_time, trade.price));
+
+        // Trades of candles which were completed already are ignored
+        if trade.time >= current.candle.start_time {
+            current.apply(trade);
+        }
+        completed
+    }
+
+    /// Applies trades received from `Ws` for the market of the builder and
+    /// returns the candles they completed.
+    pub fn update(&mut self, symbol: Option<&str>, data: &Data) -> Vec<LiveCandle> {
+        match data {
+            Data::Trade(trade) if symbol == Some(self.symbol.as_str()) => self.apply_trade(trade),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Completes the candles whose interval has passed by `now`, which
+    /// allows emitting candles while no trades arrive.
+    pub fn flush(&mut self, now: DateTime<Utc>) -> Vec<LiveCandle> {
+        self.advance(now)
+    }
+
+    /// Applies the trades of the current interval fetched through `rest`, so
+    /// that the first candle is complete after starting up. Trades which
+    /// have been applied already are skipped. Returns the candles that were
+    /// completed meanwhile.
+    pub async fn backfill(&mut self, rest: &Rest) -> rest::Result<Vec<LiveCandle>> {
+        let start_time = match &self.current {
+            Some(current) => current.candle.start_time,
+            None => rest::align(Utc::now(), self.interval.num_seconds()),
+        };
+        let mut trades: Vec<Trade> = rest
+            .paginate(GetTrades {
+                start_time: Some(start_time),
+                ..GetTrades::new(&self.symbol)
+            })
+            .try_collect()
+            .await?;
+        // Trades are paginated newest first
+        trades.reverse();
+
+        Ok(trades
+            .iter()
+            .flat_map(|trade| self.apply_trade(trade))
+            .collect())
+    }
+
+    /// The candle that is currently being built, if any trade was applied.
+    pub fn current(&self) -> Option<LiveCandle> {
+        self.current.as_ref().map(|current| self.complete(current))
+    }
+
+    /// Completes the current candle and the candles of empty intervals
+    /// until the interval containing `time`.
+    fn advance(&mut self, time: DateTime<Utc>) -> Vec<LiveCandle> {
+        let mut completed = Vec::new();
+        while let Some(current) = &self.current {
+            let end_time = current.candle.start_time + self.interval;
+            if end_time > time {
+                break;
+            }
+            completed.push(self.complete(current));
+            self.current = Some(CurrentCandle::flat(end_time, current.candle.close));
+        }
+        completed
+    }
+
+    fn complete(&self, current: &CurrentCandle) -> LiveCandle {
+        let enabled = |enabled: bool, value: Decimal| if enabled { Some(value) } else { None };
+        LiveCandle {
+            candle: current.candle,
+            trades: current.trades,
+            vwap: if self.vwap && !current.size.is_zero() {
+                Some(current.candle.volume / current.size)
+            } else {
+                None
+            },
+            buy_volume: enabled(self.side_volume, current.buy_volume),
+            sell_volume: enabled(self.side_volume, current.sell_volume),
+            liquidation_volume: enabled(self.liquidation_volume, current.liquidation_volume),
+        }
+    }
+}
@@ -21,12 +21,38 @@ pub enum Error {
     #[error("Socket is not authenticated")]
     SocketNotAuthenticated,
 
+    #[error("Connection was closed")]
+    ConnectionClosed,
+
+    #[error("{0} pings in a row were not answered")]
+    MissedPongs(u32),
+
+    /// Kept unboxed so that it can be matched and constructed directly,
+    /// which makes the error large enough for `clippy::result_large_err`
     #[error(transparent)]
     Tungstenite(#[from] tungstenite::Error),
 
     #[error(transparent)]
     Serde(#[from] serde_json::Error),
 
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[cfg(feature = "cbor")]
+    #[error("CBOR error: {0}")]
+    Cbor(String),
+
     #[error(transparent)]
     SystemTime(#[from] std::time::SystemTimeError),
 }
+
+impl Error {
+    /// Whether the error means that the connection was lost, such that no
+    /// more data will be received without reconnecting.
+    pub fn is_connection_lost(&self) -> bool {
+        matches!(
+            self,
+            Error::Tungstenite(_) | Error::ConnectionClosed | Error::MissedPongs(_)
+        )
+    }
+}
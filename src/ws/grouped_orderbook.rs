@@ -0,0 +1,113 @@
+use super::{Error, GroupedOrderbookData, Orderbook, OrderbookAction, Side, Symbol};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Represents the current state of a grouped orderbook, where every level
+/// holds the total size of all orders within a bucket of `grouping` in
+/// price. Bids are bucketed down and asks up, so that buckets never cross.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GroupedOrderbook {
+    initialized: bool,
+    pub symbol: Symbol,
+    pub grouping: Decimal,
+    pub bids: BTreeMap<Decimal, Decimal>,
+    pub asks: BTreeMap<Decimal, Decimal>,
+}
+
+impl GroupedOrderbook {
+    pub fn new(symbol: Symbol, grouping: Decimal) -> Self {
+        Self {
+            initialized: false,
+            symbol,
+            grouping,
+            bids: Default::default(),
+            asks: Default::default(),
+        }
+    }
+
+    /// Groups the levels of an orderbook locally, the same way FTX groups
+    /// the orderbooks of the grouped channel.
+    pub fn from_orderbook(orderbook: &Orderbook, grouping: Decimal) -> Self {
+        let mut grouped = Self::new(orderbook.symbol.clone(), grouping);
+        grouped.initialized = orderbook.is_initialized();
+        for (price, size) in &orderbook.bids {
+            *grouped
+                .bids
+                .entry(grouped.bucket(Side::Buy, *price))
+                .or_default() += size;
+        }
+        for (price, size) in &orderbook.asks {
+            *grouped
+                .asks
+                .entry(grouped.bucket(Side::Sell, *price))
+                .or_default() += size;
+        }
+
+        grouped
+    }
+
+    /// The price of the bucket a level of the given side belongs to.
+    pub fn bucket(&self, side: Side, price: Decimal) -> Decimal {
+        if self.grouping.is_zero() {
+            return price;
+        }
+        let buckets = price / self.grouping;
+        let buckets = match side {
+            Side::Buy => buckets.floor(),
+            Side::Sell => buckets.ceil(),
+        };
+        buckets * self.grouping
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Discards all levels, so that the orderbook expects a new partial.
+    pub fn reset(&mut self) {
+        self.initialized = false;
+        self.bids.clear();
+        self.asks.clear();
+    }
+
+    /// Applies grouped orderbook data. A partial replaces the whole
+    /// orderbook, while updates require a partial to have been applied
+    /// before.
+    #[allow(unknown_lints, clippy::result_large_err)]
+    pub fn update(&mut self, data: &GroupedOrderbookData) -> Result<(), Error> {
+        if data.action == OrderbookAction::Partial {
+            self.reset();
+            self.initialized = true;
+        } else if !self.is_initialized() {
+            return Err(Error::MissingPartial);
+        }
+
+        for (price, size) in &data.bids {
+            if size.is_zero() {
+                self.bids.remove(price);
+            } else {
+                self.bids.insert(*price, *size);
+            }
+        }
+        for (price, size) in &data.asks {
+            if size.is_zero() {
+                self.asks.remove(price);
+            } else {
+                self.asks.insert(*price, *size);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the price and quantity of the best bid bucket
+    pub fn best_bid(&self) -> Option<(&Decimal, &Decimal)> {
+        self.bids.iter().next_back()
+    }
+
+    /// Returns the price and quantity of the best ask bucket
+    pub fn best_ask(&self) -> Option<(&Decimal, &Decimal)> {
+        self.asks.iter().next()
+    }
+}
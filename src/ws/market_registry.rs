@@ -0,0 +1,118 @@
+use super::{MarketInfo, MarketsData, OrderbookAction, Symbol};
+use std::collections::HashMap;
+
+/// A change to the listed markets, as detected by `MarketRegistry::update`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MarketChange {
+    /// The market was added or enabled
+    Listed(Symbol),
+    /// The market was removed or disabled
+    Delisted(Symbol),
+    /// The price or size increment of the market changed
+    IncrementsChanged(Symbol),
+}
+
+/// The enabled markets, maintained from the data of the markets channel.
+///
+/// ```no_run
+/// # use ftx::{options::Options, ws::{Channel, Data, MarketRegistry, Ws}};
+/// # use futures::StreamExt;
+/// # async fn example() -> ftx::ws::Result<()> {
+/// let mut ws = Ws::connect(Options::default()).await?;
+/// ws.subscribe(&[Channel::Markets]).await?;
+///
+/// let mut markets = MarketRegistry::new();
+/// while let Some((_, data)) = ws.next().await.transpose()? {
+///     if let Data::Markets(data) = data {
+///         for change in markets.update(&data) {
+///             println!("{:?}", change);
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MarketRegistry {
+    initialized: bool,
+    markets: HashMap<Symbol, MarketInfo>,
+}
+
+impl MarketRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a partial has been applied.
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Applies market definitions and returns how the listed markets changed.
+    /// A partial replaces all markets, so markets missing from it are
+    /// reported as delisted. The partial that follows a reconnection
+    /// therefore only reports what changed while disconnected.
+    pub fn update(&mut self, data: &MarketsData) -> Vec<MarketChange> {
+        let mut changes = Vec::new();
+
+        if data.action == OrderbookAction::Partial {
+            self.initialized = true;
+            let delisted: Vec<_> = self
+                .markets
+                .keys()
+                .filter(|name| !data.data.contains_key(*name))
+                .cloned()
+                .collect();
+            for name in delisted {
+                self.markets.remove(&name);
+                changes.push(MarketChange::Delisted(name));
+            }
+        }
+
+        for market in data.data.values() {
+            if let Some(change) = self.apply(market) {
+                changes.push(change);
+            }
+        }
+
+        changes
+    }
+
+    fn apply(&mut self, market: &MarketInfo) -> Option<MarketChange> {
+        let name = market.name.clone();
+
+        if !market.enabled {
+            return self
+                .markets
+                .remove(&name)
+                .map(|_| MarketChange::Delisted(name));
+        }
+
+        match self.markets.insert(name.clone(), market.clone()) {
+            None => Some(MarketChange::Listed(name)),
+            Some(previous)
+                if previous.price_increment != market.price_increment
+                    || previous.size_increment != market.size_increment =>
+            {
+                Some(MarketChange::IncrementsChanged(name))
+            }
+            Some(_) => None,
+        }
+    }
+
+    pub fn get(&self, market: &str) -> Option<&MarketInfo> {
+        self.markets.get(market)
+    }
+
+    pub fn markets(&self) -> impl Iterator<Item = &MarketInfo> {
+        self.markets.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.markets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.markets.is_empty()
+    }
+}
@@ -1,20 +1,32 @@
 //! This module is used to interact with the Websocket API.
 
+mod candle_builder;
 mod error;
+mod grouped_orderbook;
+mod market_registry;
 mod model;
+mod orderbook_stream;
+mod recorder;
 #[cfg(test)]
 mod tests;
 
+pub use candle_builder::*;
 pub use error::*;
+pub use grouped_orderbook::*;
+pub use market_registry::*;
 pub use model::*;
+pub use orderbook_stream::*;
+pub use recorder::*;
 
 use crate::options::Options;
 use futures::{
+    future::BoxFuture,
     ready,
     task::{Context, Poll},
-    Future, SinkExt, Stream, StreamExt,
+    Future, FutureExt, SinkExt, Stream, StreamExt,
 };
 use hmac_sha256::HMAC;
+use rust_decimal::prelude::ToPrimitive;
 use serde_json::json;
 use std::collections::VecDeque;
 use std::pin::Pin;
@@ -24,13 +36,46 @@ use tokio::time; // 1.3.0
 use tokio::time::Interval;
 use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
 
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type Reconnection = BoxFuture<'static, Result<(WsStream, Vec<Response>)>>;
+
+/// Determines how `Ws` reconnects after the connection was lost.
+#[derive(Clone, Debug)]
+pub struct ReconnectPolicy {
+    /// Maximum number of consecutive connection attempts before giving up,
+    /// unlimited if `None`
+    pub max_attempts: Option<u32>,
+    /// Time to wait after a failed connection attempt
+    pub delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: None,
+            delay: Duration::from_secs(1),
+        }
+    }
+}
+
 pub struct Ws {
+    options: Options,
     channels: Vec<Channel>,
-    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    stream: WsStream,
     buf: VecDeque<(Option<Symbol>, Data)>,
     ping_timer: Interval,
+    /// Pings sent since the latest pong
+    unanswered_pings: u32,
+    max_missed_pongs: Option<u32>,
     /// Whether the websocket was opened authenticated with API keys or not
     is_authenticated: bool,
+    reconnect: Option<ReconnectPolicy>,
+    /// The pending reconnection, if the connection was lost
+    reconnecting: Option<Reconnection>,
+    /// Whether the connection was last lost because of missed pongs
+    missed_pongs_lost: bool,
+    /// Whether reconnecting gave up, which ends the stream
+    closed: bool,
 }
 
 impl Ws {
@@ -38,8 +83,72 @@ impl Ws {
     pub const ENDPOINT_US: &'static str = "wss://ftx.us/ws";
 
     pub async fn connect(options: Options) -> Result<Self> {
+        let (stream, is_authenticated) = Self::open(&options).await?;
+        Ok(Self {
+            options,
+            channels: Vec::new(),
+            stream,
+            buf: VecDeque::new(),
+            ping_timer: time::interval(Duration::from_secs(15)),
+            unanswered_pings: 0,
+            max_missed_pongs: None,
+            is_authenticated,
+            reconnect: None,
+            reconnecting: None,
+            missed_pongs_lost: false,
+            closed: false,
+        })
+    }
+
+    /// Reconnect automatically when the connection is lost, instead of
+    /// returning an error from the stream. `Data::Disconnected` is returned
+    /// from the stream as soon as the connection is lost. After reconnecting,
+    /// the socket is authenticated again, all subscribed channels are
+    /// subscribed to again, and `Data::Reconnected` is returned. Once
+    /// `policy.max_attempts` failed, the stream returns the last error and
+    /// ends.
+    #[must_use]
+    pub fn with_reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    /// Send a ping every `interval` instead of every 15 seconds
+    #[must_use]
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_timer = time::interval(interval);
+        self
+    }
+
+    /// Consider the connection lost once `max` pings in a row have not been
+    /// answered by a pong, even if the socket itself is still open. The
+    /// stream then returns `Error::MissedPongs`, or reconnects if enabled.
+    #[must_use]
+    pub fn with_max_missed_pongs(mut self, max: u32) -> Self {
+        self.max_missed_pongs = Some(max);
+        self
+    }
+
+    /// Whether the websocket was opened with API keys
+    pub fn is_authenticated(&self) -> bool {
+        self.is_authenticated
+    }
+
+    /// Whether the latest `Data::Disconnected` was caused by missed pongs
+    /// rather than by the socket failing
+    pub(crate) fn lost_to_missed_pongs(&self) -> bool {
+        self.missed_pongs_lost
+    }
+
+    /// The currently subscribed `Channel`s
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    /// Opens a websocket and authenticates it if the options contain API keys
+    async fn open(options: &Options) -> Result<(WsStream, bool)> {
         let (mut stream, _) = connect_async(options.endpoint.ws()).await?;
-        let is_authenticated = if let (Some(key), Some(secret)) = (options.key, options.secret) {
+        let is_authenticated = if let (Some(key), Some(secret)) = (&options.key, &options.secret) {
             let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
             let sign_payload = format!("{}websocket_login", timestamp);
             let sign = HMAC::mac(sign_payload.as_bytes(), secret.as_bytes());
@@ -63,16 +172,73 @@ impl Ws {
         } else {
             false
         };
-        Ok(Self {
-            channels: Vec::new(),
-            stream,
-            buf: VecDeque::new(),
-            ping_timer: time::interval(Duration::from_secs(15)),
-            is_authenticated,
-        })
+        Ok((stream, is_authenticated))
+    }
+
+    /// Opens a new websocket and subscribes to `channels`, retrying according
+    /// to `policy`. Responses received before all subscriptions were
+    /// confirmed are returned alongside the stream.
+    async fn reopen(
+        options: Options,
+        channels: Vec<Channel>,
+        policy: ReconnectPolicy,
+    ) -> Result<(WsStream, Vec<Response>)> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match Self::resubscribe(&options, &channels).await {
+                Ok(reopened) => return Ok(reopened),
+                Err(e) if !matches!(policy.max_attempts, Some(max) if attempts >= max) => {
+                    log::warn!("Reconnection attempt {} failed: {}", attempts, e);
+                    time::sleep(policy.delay).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn resubscribe(
+        options: &Options,
+        channels: &[Channel],
+    ) -> Result<(WsStream, Vec<Response>)> {
+        let (mut stream, _) = Self::open(options).await?;
+        let mut responses = Vec::new();
+
+        'channels: for channel in channels {
+            stream
+                .send(Self::channel_message("subscribe", channel))
+                .await?;
+
+            // Confirmation should arrive within the next 100 updates
+            for _ in 0..100 {
+                let msg = stream.next().await.ok_or(Error::ConnectionClosed)??;
+                match Self::parse_message(msg)? {
+                    Some(Response {
+                        r#type: Type::Subscribed,
+                        ..
+                    }) => continue 'channels,
+                    Some(Response {
+                        r#type: Type::Pong, ..
+                    })
+                    | None => {}
+                    Some(response) => responses.push(response),
+                }
+            }
+
+            return Err(Error::MissingSubscriptionConfirmation);
+        }
+
+        Ok((stream, responses))
     }
 
     async fn ping(&mut self) -> Result<()> {
+        if let Some(max) = self.max_missed_pongs {
+            if self.unanswered_pings >= max {
+                return Err(Error::MissedPongs(self.unanswered_pings));
+            }
+        }
+        self.unanswered_pings += 1;
+
         self.stream
             .send(Message::Text(
                 json!({
@@ -142,24 +308,7 @@ impl Ws {
         };
 
         'channels: for channel in channels {
-            let (channel, symbol) = match channel {
-                Channel::Orderbook(symbol) => ("orderbook", symbol.as_str()),
-                Channel::Trades(symbol) => ("trades", symbol.as_str()),
-                Channel::Ticker(symbol) => ("ticker", symbol.as_str()),
-                Channel::Fills => ("fills", ""),
-                Channel::Orders => ("orders", ""),
-            };
-
-            self.stream
-                .send(Message::Text(
-                    json!({
-                        "op": op,
-                        "channel": channel,
-                        "market": symbol,
-                    })
-                    .to_string(),
-                ))
-                .await?;
+            self.stream.send(Self::channel_message(op, channel)).await?;
 
             // Confirmation should arrive within the next 100 updates
             for _ in 0..100 {
@@ -192,24 +341,68 @@ impl Ws {
         Ok(())
     }
 
+    fn channel_message(op: &str, channel: &Channel) -> Message {
+        let (channel, symbol) = match channel {
+            Channel::Orderbook(symbol) => ("orderbook", symbol.as_str()),
+            Channel::Trades(symbol) => ("trades", symbol.as_str()),
+            Channel::Ticker(symbol) => ("ticker", symbol.as_str()),
+            Channel::Fills => ("fills", ""),
+            Channel::Orders => ("orders", ""),
+            Channel::Markets => ("markets", ""),
+            Channel::GroupedOrderbook { market, grouping } => {
+                return Message::Text(
+                    json!({
+                        "op": op,
+                        "channel": "orderbookGrouped",
+                        "market": market,
+                        // Sent as a number, unlike decimals elsewhere
+                        "grouping": grouping.to_f64(),
+                    })
+                    .to_string(),
+                );
+            }
+        };
+
+        Message::Text(
+            json!({
+                "op": op,
+                "channel": channel,
+                "market": symbol,
+            })
+            .to_string(),
+        )
+    }
+
+    /// Parses a text message, skipping other messages
+    #[allow(unknown_lints, clippy::result_large_err)]
+    fn parse_message(msg: Message) -> Result<Option<Response>> {
+        if let Message::Text(text) = msg {
+            // println!("{}", text); // Uncomment for debugging
+            let response: Response = serde_json::from_str(&text)?;
+            return Ok(Some(response));
+        }
+
+        Ok(None)
+    }
+
     async fn next_response(&mut self) -> Result<Response> {
         loop {
             tokio::select! {
                 _ = self.ping_timer.tick() => {
                     self.ping().await?;
                 },
-                Some(msg) = self.stream.next() => {
-                    let msg = msg?;
-                    if let Message::Text(text) = msg {
-                        // println!("{}", text); // Uncomment for debugging
-                        let response: Response = serde_json::from_str(&text)?;
-
+                msg = self.stream.next() => {
+                    let msg = msg.ok_or(Error::ConnectionClosed)??;
+                    // Nothing can be sent after the server closed the
+                    // connection, so don't wait for the stream to end
+                    if let Message::Close(_) = msg {
+                        return Err(Error::ConnectionClosed);
+                    }
+                    match Self::parse_message(msg)? {
                         // Don't return Pong responses
-                        if let Response { r#type: Type::Pong, .. } = response {
-                            continue;
-                        }
-
-                        return Ok(response)
+                        Some(Response { r#type: Type::Pong, .. }) => self.unanswered_pings = 0,
+                        Some(response) => return Ok(response),
+                        None => {}
                     }
                 },
             }
@@ -232,6 +425,13 @@ impl Ws {
                     self.buf
                         .push_back((response.market, Data::OrderbookData(orderbook)));
                 }
+                ResponseData::GroupedOrderbookData(mut orderbook) => {
+                    if let Type::Partial = response.r#type {
+                        orderbook.action = OrderbookAction::Partial;
+                    }
+                    self.buf
+                        .push_back((response.market, Data::GroupedOrderbookData(orderbook)));
+                }
                 ResponseData::Fill(fill) => {
                     self.buf.push_back((response.market, Data::Fill(fill)));
                 }
@@ -241,6 +441,10 @@ impl Ws {
                 ResponseData::Order(order) => {
                     self.buf.push_back((response.market, Data::Order(order)));
                 }
+                ResponseData::Markets(markets) => {
+                    self.buf
+                        .push_back((response.market, Data::Markets(markets)));
+                }
             }
         }
     }
@@ -254,17 +458,49 @@ impl Stream for Ws {
             if let Some(data) = self.buf.pop_front() {
                 return Poll::Ready(Some(Ok(data)));
             }
+            if self.closed {
+                return Poll::Ready(None);
+            }
+            if let Some(reconnecting) = self.reconnecting.as_mut() {
+                // The reconnection future is stored, so that it makes progress
+                // across polls.
+                let reconnected = ready!(reconnecting.poll_unpin(cx));
+                self.reconnecting = None;
+                match reconnected {
+                    Ok((stream, responses)) => {
+                        self.stream = stream;
+                        self.unanswered_pings = 0;
+                        self.buf.push_back((None, Data::Reconnected));
+                        for response in responses {
+                            self.handle_response(response);
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        self.closed = true;
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                }
+            }
             let response = {
                 // Fetch new response if buffer is empty.
                 // safety: this is ok because the future from self.next_response() will only live in this function.
                 // It won't be moved anymore.
                 let mut next_response = self.next_response();
                 let pinned = unsafe { Pin::new_unchecked(&mut next_response) };
-                match ready!(pinned.poll(cx)) {
-                    Ok(response) => response,
-                    Err(e) => {
-                        return Poll::Ready(Some(Err(e)));
-                    }
+                ready!(pinned.poll(cx))
+            };
+            let response = match (response, self.reconnect.clone()) {
+                (Ok(response), _) => response,
+                (Err(e), Some(policy)) if e.is_connection_lost() => {
+                    log::warn!("Connection lost, reconnecting: {}", e);
+                    self.missed_pongs_lost = matches!(e, Error::MissedPongs(_));
+                    let reopen = Self::reopen(self.options.clone(), self.channels.clone(), policy);
+                    self.reconnecting = Some(reopen.boxed());
+                    return Poll::Ready(Some(Ok((None, Data::Disconnected))));
+                }
+                (Err(e), _) => {
+                    return Poll::Ready(Some(Err(e)));
                 }
             };
             // Handle the response, possibly adding to the buffer
@@ -1,11 +1,16 @@
-pub use crate::rest::{Coin, Id, MarketType, OrderInfo, Side, Symbol, Trade};
+use crate::rest;
+pub use crate::rest::{
+    Coin, Fill, Id, Liquidity, MarketType, OrderInfo, OrderbookView, Side, Symbol, Trade,
+};
 use chrono::{DateTime, Utc};
 use crc32fast::Hasher;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
-use serde_with::{serde_as, TimestampSecondsWithFrac};
-use std::{collections::BTreeMap, ops::Not};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Not,
+};
 
 use super::Error;
 
@@ -17,6 +22,15 @@ pub enum Channel {
     Ticker(Symbol),
     Fills,
     Orders,
+    /// Definitions of all markets, followed by changes to them
+    Markets,
+    /// The orderbook of a market with its levels aggregated into buckets of
+    /// `grouping` in price. Unlike `Orderbook`, it is not limited to the
+    /// best 100 levels, but has no checksum.
+    GroupedOrderbook {
+        market: Symbol,
+        grouping: Decimal,
+    },
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -48,21 +62,31 @@ pub enum ResponseData {
     Ticker(Ticker),
     Trades(Vec<Trade>),
     OrderbookData(OrderbookData),
+    GroupedOrderbookData(GroupedOrderbookData),
     Fill(Fill),
     Order(OrderInfo),
+    Markets(MarketsData),
 }
 
 /// Represents the data we return to the user
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Data {
     Ticker(Ticker),
     Trade(Trade),
     OrderbookData(OrderbookData),
+    GroupedOrderbookData(GroupedOrderbookData),
     Fill(Fill),
     Order(OrderInfo),
+    Markets(MarketsData),
+    /// The connection was lost, and reconnecting has started. Updates are
+    /// missed until `Reconnected` is returned.
+    Disconnected,
+    /// The connection was lost and has been reestablished, and all channels
+    /// have been subscribed to again. Updates may have been missed in the
+    /// meantime, and every orderbook channel starts over with a partial.
+    Reconnected,
 }
 
-#[serde_as]
 #[derive(Copy, Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Ticker {
@@ -71,13 +95,23 @@ pub struct Ticker {
     pub bid_size: Decimal,
     pub ask_size: Decimal,
     pub last: Decimal,
-    #[serde_as(as = "TimestampSecondsWithFrac<f64>")]
+    #[serde(with = "timestamp_seconds")]
     pub time: DateTime<Utc>,
 }
 
+/// The best bid and ask of a ticker, as an orderbook of one level per side
+impl OrderbookView for Ticker {
+    fn bid_levels(&self) -> Box<dyn Iterator<Item = (Decimal, Decimal)> + '_> {
+        Box::new(std::iter::once((self.bid, self.bid_size)))
+    }
+
+    fn ask_levels(&self) -> Box<dyn Iterator<Item = (Decimal, Decimal)> + '_> {
+        Box::new(std::iter::once((self.ask, self.ask_size)))
+    }
+}
+
 /// Order book data received from FTX which is used for initializing and updating
 /// the OrderBook struct
-#[serde_as]
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderbookData {
@@ -87,11 +121,75 @@ pub struct OrderbookData {
     pub bids: Vec<(Decimal, Decimal)>,
     pub asks: Vec<(Decimal, Decimal)>,
     pub checksum: Checksum,
-    #[serde_as(as = "TimestampSecondsWithFrac<f64>")]
+    #[serde(with = "timestamp_seconds")]
     pub time: DateTime<Utc>, // API returns 1621740952.5079553
 }
 
-type Checksum = u32;
+pub type Checksum = u32;
+
+impl OrderbookData {
+    /// A partial with the levels of a snapshot of `GetOrderBook`, e.g. to
+    /// initialize an `Orderbook` through the same code path as the partials
+    /// of the orderbook channel.
+    pub fn from_snapshot(snapshot: &rest::Orderbook) -> Self {
+        Self {
+            action: OrderbookAction::Partial,
+            bids: snapshot.bids.clone(),
+            asks: snapshot.asks.clone(),
+            checksum: Orderbook::from_snapshot(Symbol::new(), snapshot).checksum(),
+            time: Utc::now(),
+        }
+    }
+}
+
+/// (De)serializes times as floating point seconds, the way FTX sends them.
+/// Times are rounded to microseconds, so that they are the same after being
+/// serialized again, e.g. by a `Recorder`.
+mod timestamp_seconds {
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        time: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let micros = f64::from(time.timestamp_subsec_micros());
+        serializer.serialize_f64(time.timestamp() as f64 + micros / 1e6)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let value = f64::deserialize(deserializer)?;
+        let mut seconds = value.floor();
+        let mut micros = ((value - seconds) * 1e6).round();
+        if micros >= 1e6 {
+            seconds += 1.0;
+            micros -= 1e6;
+        }
+        Utc.timestamp_opt(seconds as i64, micros as u32 * 1000)
+            .single()
+            .ok_or_else(|| D::Error::custom(format!("invalid timestamp {}", value)))
+    }
+}
+
+/// Grouped orderbook data received from FTX, used for initializing and
+/// updating a `GroupedOrderbook`. Levels are keyed by the price of their
+/// bucket, and a size of zero removes a bucket.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupedOrderbookData {
+    // Grouped data does not carry an action, which is taken from the type of
+    // the response instead
+    #[serde(default = "update_action")]
+    pub action: OrderbookAction,
+    pub bids: Vec<(Decimal, Decimal)>,
+    pub asks: Vec<(Decimal, Decimal)>,
+}
+
+fn update_action() -> OrderbookAction {
+    OrderbookAction::Update
+}
 
 #[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
@@ -105,7 +203,7 @@ pub enum OrderbookAction {
 /// Represents the current state of the orderbook, guaranteed to be accurate
 /// up to the best 100 bids and best 100 asks since the latest update.
 /// Supports efficient insertions, updates, and deletions via a BTreeMap.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Orderbook {
     initialized: bool,
     pub symbol: Symbol,
@@ -138,10 +236,21 @@ impl Orderbook {
         }
     }
 
+    /// Creates an initialized orderbook from a snapshot of `GetOrderBook`,
+    /// as if its levels had been received as a partial.
+    pub fn from_snapshot(symbol: Symbol, snapshot: &rest::Orderbook) -> Orderbook {
+        let mut orderbook = Orderbook::new(symbol);
+        orderbook.initialized = true;
+        orderbook.bids.extend(snapshot.bids.iter().copied());
+        orderbook.asks.extend(snapshot.asks.iter().copied());
+        orderbook
+    }
+
     pub fn is_initialized(&self) -> bool {
         self.initialized
     }
 
+    #[allow(unknown_lints, clippy::result_large_err)]
     fn apply(&mut self, data: &OrderbookData) -> Result<(), Error> {
         self.bids.extend(data.bids.iter().cloned());
         self.asks.extend(data.asks.iter().cloned());
@@ -156,23 +265,39 @@ impl Orderbook {
         }
     }
 
+    /// Discards all levels, so that the orderbook expects a new partial.
+    pub fn reset(&mut self) {
+        self.initialized = false;
+        self.bids.clear();
+        self.asks.clear();
+    }
+
+    /// Applies orderbook data. A partial replaces the whole orderbook, while
+    /// updates require a partial to have been applied before.
+    #[allow(unknown_lints, clippy::result_large_err)]
     pub fn update(&mut self, data: &OrderbookData) -> Result<(), Error> {
-        if self.is_initialized() {
-            self.apply(data)
-        } else if data.action == OrderbookAction::Partial {
+        if data.action == OrderbookAction::Partial {
+            self.reset();
             self.initialized = true;
             self.apply(data)
+        } else if self.is_initialized() {
+            self.apply(data)
         } else {
             Err(Error::MissingPartial)
         }
     }
 
     pub fn verify_checksum(&self, checksum: &Checksum) -> bool {
+        self.checksum() == *checksum
+    }
+
+    /// Computes the CRC32 checksum of the best 100 bids and asks the same
+    /// way FTX does.
+    pub fn checksum(&self) -> Checksum {
         let input = (0..100)
-            .into_iter()
             .zip(self.bids.iter().rev().zip(self.asks.iter()))
             .map(|(_, ((b_p, b_s), (a_p, a_s)))| {
-                vec![
+                [
                     format_value(b_p),
                     format_value(b_s),
                     format_value(a_p),
@@ -187,10 +312,7 @@ impl Orderbook {
 
         let mut hasher = Hasher::new();
         hasher.update(input);
-        let output = hasher.finalize();
-
-        // println!("Output: {}, Checksum: {}", output, checksum);
-        output == *checksum
+        hasher.finalize()
     }
 
     /// Returns the price of the best bid
@@ -229,66 +351,215 @@ impl Orderbook {
     }
 
     /// Returns the expected execution price of a market order given the current
-    /// orders in the order book. Returns None if the order size exceeds the
-    /// liquidity available on that side of the order book.
+    /// orders in the order book. Returns None if the quantity is not positive
+    /// or exceeds the liquidity available on that side of the order book.
     pub fn quote(&self, side: Side, quantity: Decimal) -> Option<Decimal> {
-        // Step 1: Match with orders in the book
-        let mut bids_iter = self.bids.iter().rev();
-        let mut asks_iter = self.asks.iter();
+        OrderbookView::quote(self, side, quantity)
+    }
 
-        let mut fills: Vec<(Decimal, Decimal)> = Vec::new(); // (price, quantity)
-        let mut remaining = quantity;
+    /// Returns the levels a market order of the given side would match
+    /// against, best price first, i.e. the asks for buy orders and the bids
+    /// for sell orders.
+    pub fn levels(&self, side: Side) -> Box<dyn Iterator<Item = (&Decimal, &Decimal)> + '_> {
+        match side {
+            Side::Buy => Box::new(self.asks.iter()),
+            Side::Sell => Box::new(self.bids.iter().rev()),
+        }
+    }
 
-        while remaining.is_zero().not() && remaining.is_sign_positive() {
-            let (price, quantity) = match side {
-                Side::Buy => asks_iter.next()?,
-                Side::Sell => bids_iter.next()?,
-            };
+    /// Returns the levels a market order of the given side and quantity
+    /// would match against, up to and including the level that fills it.
+    pub fn levels_until(
+        &self,
+        side: Side,
+        quantity: Decimal,
+    ) -> impl Iterator<Item = (&Decimal, &Decimal)> + '_ {
+        self.levels(side)
+            .scan(dec!(0), move |filled, (price, size)| {
+                if *filled >= quantity {
+                    return None;
+                }
+                *filled += size;
+                Some((price, size))
+            })
+    }
+
+    /// Returns the cumulative quantity of bids and asks priced within `bps`
+    /// basis points of the mid price.
+    /// (bid_quantity, ask_quantity)
+    pub fn depth(&self, bps: Decimal) -> Option<(Decimal, Decimal)> {
+        let mid = self.mid_price()?;
+        let distance = mid * bps / dec!(10000);
+
+        let bids = self
+            .bids
+            .range(mid - distance..)
+            .map(|(_, size)| size)
+            .sum();
+        let asks = self
+            .asks
+            .range(..=mid + distance)
+            .map(|(_, size)| size)
+            .sum();
+        Some((bids, asks))
+    }
+
+    /// Returns the imbalance between the quantity of bids and asks within
+    /// `bps` basis points of the mid price, from -1 if there are only asks to
+    /// 1 if there are only bids.
+    pub fn imbalance(&self, bps: Decimal) -> Option<Decimal> {
+        let (bids, asks) = self.depth(bps)?;
+        let total = bids + asks;
+        if total.is_zero() {
+            return None;
+        }
+        Some((bids - asks) / total)
+    }
+
+    /// Returns the expected execution price of a market order that spends or
+    /// receives `notional` in the quote currency. Returns None if the
+    /// notional is not positive or exceeds the liquidity available on that
+    /// side of the order book.
+    pub fn quote_notional(&self, side: Side, notional: Decimal) -> Option<Decimal> {
+        if notional <= dec!(0) {
+            return None;
+        }
 
-            if *quantity <= remaining {
-                remaining -= quantity;
-                fills.push((*price, *quantity));
+        let mut levels = self.levels(side);
+        let mut remaining = notional;
+        let mut quantity = dec!(0);
+
+        while remaining > dec!(0) {
+            let (price, size) = levels.next()?;
+            let level_notional = price * size;
+            if level_notional <= remaining {
+                remaining -= level_notional;
+                quantity += size;
             } else {
-                fills.push((*price, remaining));
+                quantity += remaining / price;
                 remaining = dec!(0);
             }
         }
 
-        // Step 2: Compute the weighted average
-        let dot_product = fills
-            .iter()
-            .fold(dec!(0), |acc, (price, quantity)| acc + (price * quantity));
+        if quantity.is_zero() {
+            return None;
+        }
+        Some(notional / quantity)
+    }
+
+    /// Returns the price impact of market orders of the given side for each
+    /// of the quantities, in the same order. Returns None for quantities
+    /// that exceed the liquidity available on that side of the order book.
+    /// The levels are only walked once, regardless of the number of
+    /// quantities.
+    pub fn price_impact(&self, side: Side, quantities: &[Decimal]) -> Vec<Option<PriceImpact>> {
+        let mut impacts = vec![None; quantities.len()];
+        let mid = match self.mid_price() {
+            Some(mid) => mid,
+            None => return impacts,
+        };
+
+        let mut order: Vec<usize> = (0..quantities.len()).collect();
+        order.sort_by_key(|&i| quantities[i]);
+
+        let mut levels = self.levels(side);
+        // The price and unfilled quantity of the current level
+        let mut level: Option<(Decimal, Decimal)> = None;
+        let mut filled = dec!(0);
+        let mut notional = dec!(0);
+
+        for i in order {
+            let quantity = quantities[i];
+            while filled < quantity {
+                let (price, size) = match level.take() {
+                    Some(level) => level,
+                    None => match levels.next() {
+                        Some((price, size)) => (*price, *size),
+                        None => return impacts,
+                    },
+                };
+                let matched = size.min(quantity - filled);
+                filled += matched;
+                notional += price * matched;
+                if matched < size {
+                    level = Some((price, size - matched));
+                }
+            }
+
+            let price = if filled.is_zero() {
+                match self.levels(side).next() {
+                    Some((price, _)) => *price,
+                    None => return impacts,
+                }
+            } else {
+                notional / filled
+            };
+            let slippage = match side {
+                Side::Buy => price - mid,
+                Side::Sell => mid - price,
+            };
+            impacts[i] = Some(PriceImpact {
+                quantity,
+                price,
+                bps: slippage / mid * dec!(10000),
+            });
+        }
+
+        impacts
+    }
+}
+
+impl OrderbookView for Orderbook {
+    fn bid_levels(&self) -> Box<dyn Iterator<Item = (Decimal, Decimal)> + '_> {
+        Box::new(self.bids.iter().rev().map(|(price, size)| (*price, *size)))
+    }
+
+    fn ask_levels(&self) -> Box<dyn Iterator<Item = (Decimal, Decimal)> + '_> {
+        Box::new(self.asks.iter().map(|(price, size)| (*price, *size)))
+    }
 
-        Some(dot_product / quantity)
+    fn depth(&self, bps: Decimal) -> Option<(Decimal, Decimal)> {
+        Orderbook::depth(self, bps)
     }
 }
 
+/// The expected execution of a market order, as returned by
+/// `Orderbook::price_impact`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PriceImpact {
+    pub quantity: Decimal,
+    /// The average execution price
+    pub price: Decimal,
+    /// How much worse the average execution price is than the mid price, in
+    /// basis points
+    pub bps: Decimal,
+}
+
+/// Market definitions received on the markets channel. A partial contains
+/// all markets, while updates only contain the markets that changed.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Fill {
-    pub id: Id,
-    pub market: Option<Symbol>,
-    pub future: Option<Symbol>,
-    pub base_currency: Option<Coin>,
-    pub quote_currency: Option<Coin>,
-    pub r#type: String, // e.g. "order"
-    pub side: Side,
-    pub price: Decimal,
-    pub size: Decimal,
-    pub order_id: Option<Id>,
-    pub trade_id: Option<Id>,
-    pub time: DateTime<Utc>,
-    pub fee: Decimal,
-    pub fee_rate: Decimal,
-    pub fee_currency: Coin,
-    pub liquidity: Liquidity,
+pub struct MarketsData {
+    pub action: OrderbookAction,
+    pub data: HashMap<Symbol, MarketInfo>,
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+/// The definition of a market as published on the markets channel
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
-pub enum Liquidity {
-    Maker,
-    Taker,
+pub struct MarketInfo {
+    pub name: Symbol,
+    pub enabled: bool,
+    #[serde(rename = "type")]
+    pub market_type: MarketType,
+    pub underlying: Option<Coin>,
+    pub base_currency: Option<Coin>,
+    pub quote_currency: Option<Coin>,
+    pub price_increment: Decimal,
+    pub size_increment: Decimal,
+    pub restricted: bool,
+    #[serde(default)]
+    pub post_only: bool,
 }
 
 #[cfg(test)]
@@ -0,0 +1,193 @@
+use super::{Channel, Data, Error, Orderbook, OrderbookAction, OrderbookData, Result, Symbol, Ws};
+use futures::{
+    future::BoxFuture,
+    ready,
+    task::{Context, Poll},
+    FutureExt, Stream, StreamExt,
+};
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+
+/// An event of an `OrderbookStream`
+#[derive(Clone, Debug)]
+pub enum OrderbookEvent {
+    /// A partial was applied, which replaced the whole orderbook
+    Snapshot(Orderbook),
+    /// An update was applied to the orderbook of the market and its checksum
+    /// was verified. The resulting orderbook is available through
+    /// `OrderbookStream::orderbook`.
+    Update(Symbol, OrderbookData),
+    /// The orderbook of the market became inconsistent, e.g. because of an
+    /// incorrect checksum or a reconnection. It was discarded and a new
+    /// partial will follow as `Snapshot`.
+    Resyncing(Symbol),
+}
+
+type Resync = BoxFuture<'static, (Ws, Result<()>)>;
+
+/// Maintains one `Orderbook` per subscribed market on top of `Ws`.
+///
+/// Updates are applied as they arrive. When a checksum does not match, the
+/// orderbook channel is unsubscribed and subscribed again to receive a fresh
+/// partial, so that consumers only ever see consistent orderbooks.
+pub struct OrderbookStream {
+    /// `None` while the channel of a market is being resubscribed
+    ws: Option<Ws>,
+    orderbooks: HashMap<Symbol, Orderbook>,
+    events: VecDeque<OrderbookEvent>,
+    resync: Option<Resync>,
+}
+
+impl OrderbookStream {
+    pub fn new(ws: Ws) -> Self {
+        Self {
+            ws: Some(ws),
+            orderbooks: HashMap::new(),
+            events: VecDeque::new(),
+            resync: None,
+        }
+    }
+
+    /// Subscribe to the orderbooks of the specified markets
+    pub async fn subscribe(&mut self, symbols: &[Symbol]) -> Result<()> {
+        let channels: Vec<_> = symbols.iter().cloned().map(Channel::Orderbook).collect();
+        self.ws().await?.subscribe(&channels).await?;
+        for symbol in symbols {
+            self.orderbooks
+                .insert(symbol.clone(), Orderbook::new(symbol.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Unsubscribe from the orderbooks of the specified markets
+    pub async fn unsubscribe(&mut self, symbols: &[Symbol]) -> Result<()> {
+        let channels: Vec<_> = symbols.iter().cloned().map(Channel::Orderbook).collect();
+        self.ws().await?.unsubscribe(&channels).await?;
+        for symbol in symbols {
+            self.orderbooks.remove(symbol);
+        }
+
+        Ok(())
+    }
+
+    /// The current orderbook of a market. It is not initialized while
+    /// waiting for a partial.
+    pub fn orderbook(&self, symbol: &str) -> Option<&Orderbook> {
+        self.orderbooks.get(symbol)
+    }
+
+    pub fn orderbooks(&self) -> impl Iterator<Item = &Orderbook> {
+        self.orderbooks.values()
+    }
+
+    /// The underlying websocket, once a pending resync has finished
+    async fn ws(&mut self) -> Result<&mut Ws> {
+        if let Some(resync) = self.resync.take() {
+            let (ws, result) = resync.await;
+            self.ws = Some(ws);
+            result?;
+        }
+
+        Ok(self
+            .ws
+            .as_mut()
+            .expect("websocket is only taken while resyncing"))
+    }
+
+    #[allow(unknown_lints, clippy::result_large_err)]
+    fn handle_orderbook_data(&mut self, symbol: Symbol, data: OrderbookData) -> Result<()> {
+        let orderbook = match self.orderbooks.get_mut(&symbol) {
+            Some(orderbook) => orderbook,
+            None => return Ok(()),
+        };
+
+        match orderbook.update(&data) {
+            Ok(()) if data.action == OrderbookAction::Partial => {
+                self.events
+                    .push_back(OrderbookEvent::Snapshot(orderbook.clone()));
+            }
+            Ok(()) => {
+                self.events.push_back(OrderbookEvent::Update(symbol, data));
+            }
+            // Updates of a discarded orderbook that were sent before the
+            // channel was resubscribed
+            Err(Error::MissingPartial) => {}
+            Err(Error::IncorrectChecksum) => {
+                log::warn!("Incorrect orderbook checksum for {}, resyncing", symbol);
+                orderbook.reset();
+                self.events
+                    .push_back(OrderbookEvent::Resyncing(symbol.clone()));
+
+                let mut ws = self
+                    .ws
+                    .take()
+                    .expect("websocket is only taken while resyncing");
+                self.resync = Some(
+                    async move {
+                        let channels = [Channel::Orderbook(symbol)];
+                        let result = match ws.unsubscribe(&channels).await {
+                            Ok(()) => ws.subscribe(&channels).await,
+                            Err(e) => Err(e),
+                        };
+                        (ws, result)
+                    }
+                    .boxed(),
+                );
+            }
+            Err(e) => return Err(e),
+        }
+
+        Ok(())
+    }
+
+    fn handle_reconnected(&mut self) {
+        // Ws has subscribed to all channels again, so a partial follows for
+        // every orderbook
+        for orderbook in self.orderbooks.values_mut() {
+            orderbook.reset();
+            self.events
+                .push_back(OrderbookEvent::Resyncing(orderbook.symbol.clone()));
+        }
+    }
+}
+
+impl Stream for OrderbookStream {
+    type Item = Result<OrderbookEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            if let Some(resync) = self.resync.as_mut() {
+                let (ws, result) = ready!(resync.poll_unpin(cx));
+                self.resync = None;
+                self.ws = Some(ws);
+                if let Err(e) = result {
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+
+            let ws = self
+                .ws
+                .as_mut()
+                .expect("websocket is only taken while resyncing");
+            let result = match ready!(ws.poll_next_unpin(cx)) {
+                Some(Ok((Some(symbol), Data::OrderbookData(data)))) => {
+                    self.handle_orderbook_data(symbol, data)
+                }
+                Some(Ok((_, Data::Reconnected))) => {
+                    self.handle_reconnected();
+                    Ok(())
+                }
+                Some(Ok(_)) => Ok(()),
+                Some(Err(e)) => Err(e),
+                None => return Poll::Ready(None),
+            };
+            if let Err(e) = result {
+                return Poll::Ready(Some(Err(e)));
+            }
+        }
+    }
+}
@@ -0,0 +1,274 @@
+#[cfg(feature = "cbor")]
+use super::Error;
+use super::{Data, Result, Symbol};
+use chrono::{DateTime, Utc};
+use futures::{
+    ready,
+    task::{Context, Poll},
+    Future, Stream,
+};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+/// How recordings are stored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// One JSON object per line
+    JsonLines,
+    /// A sequence of CBOR values, which is more compact than JSON
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+/// Data received by `Ws`, as stored by a `Recorder`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Record {
+    /// When the data was received
+    pub time: DateTime<Utc>,
+    pub symbol: Option<Symbol>,
+    pub data: Data,
+}
+
+/// Serializes like `Record`, without cloning the data
+#[derive(Serialize)]
+struct RecordRef<'a> {
+    time: DateTime<Utc>,
+    symbol: Option<&'a str>,
+    data: &'a Data,
+}
+
+/// Writes the orderbook data, trades and tickers received by `Ws` to an
+/// append-only recording, which can be replayed with a `Player`.
+///
+/// Disconnections and reconnections are recorded as well, so that a replay
+/// resynchronizes orderbooks the same way the live stream required. Other
+/// data, like fills and orders, is skipped.
+///
+/// ```no_run
+/// # use ftx::{options::Options, ws::{Channel, Recorder, RecordingFormat, Ws}};
+/// # use futures::StreamExt;
+/// # async fn example() -> ftx::ws::Result<()> {
+/// let mut ws = Ws::connect(Options::default()).await?;
+/// ws.subscribe(&[Channel::Orderbook("BTC-PERP".to_owned())]).await?;
+///
+/// let mut recorder = Recorder::create("btc-perp.jsonl", RecordingFormat::JsonLines)?;
+/// while let Some((symbol, data)) = ws.next().await.transpose()? {
+///     recorder.record(symbol.as_deref(), &data)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Recorder<W: Write> {
+    writer: W,
+    format: RecordingFormat,
+}
+
+impl Recorder<BufWriter<File>> {
+    /// Appends to the file at `path`, creating it if it does not exist.
+    #[allow(unknown_lints, clippy::result_large_err)]
+    pub fn create(path: impl AsRef<Path>, format: RecordingFormat) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(BufWriter::new(file), format))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(writer: W, format: RecordingFormat) -> Self {
+        Self { writer, format }
+    }
+
+    /// Records data received now. Returns whether it was recorded.
+    #[allow(unknown_lints, clippy::result_large_err)]
+    pub fn record(&mut self, symbol: Option<&str>, data: &Data) -> Result<bool> {
+        self.record_at(Utc::now(), symbol, data)
+    }
+
+    /// Records data received at the given time. Returns whether it was
+    /// recorded.
+    #[allow(unknown_lints, clippy::result_large_err)]
+    pub fn record_at(
+        &mut self,
+        time: DateTime<Utc>,
+        symbol: Option<&str>,
+        data: &Data,
+    ) -> Result<bool> {
+        if !is_recorded(data) {
+            return Ok(false);
+        }
+
+        let record = RecordRef { time, symbol, data };
+        match self.format {
+            RecordingFormat::JsonLines => {
+                serde_json::to_writer(&mut self.writer, &record)?;
+                self.writer.write_all(b"\n")?;
+            }
+            #[cfg(feature = "cbor")]
+            RecordingFormat::Cbor => ciborium::ser::into_writer(&record, &mut self.writer)
+                .map_err(|e| Error::Cbor(e.to_string()))?,
+        }
+        Ok(true)
+    }
+
+    /// Writes buffered records, e.g. before the process exits.
+    #[allow(unknown_lints, clippy::result_large_err)]
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn is_recorded(data: &Data) -> bool {
+    matches!(
+        data,
+        Data::OrderbookData(_)
+            | Data::Trade(_)
+            | Data::Ticker(_)
+            | Data::Disconnected
+            | Data::Reconnected
+    )
+}
+
+/// Replays a recording of a `Recorder` as a stream of the same items as
+/// `Ws`.
+///
+/// By default, the data is returned with the same delays in between as it
+/// was received. The replay can be accelerated with `with_speed`, or run
+/// as fast as possible with `without_delays`.
+///
+/// Records are read from the reader within `poll_next`, which blocks the
+/// task until they are read. That is brief for a buffered local file, but a
+/// reader which can block for longer, e.g. on a network filesystem, should
+/// be replayed on a thread of its own, like with `tokio::task::spawn_blocking`.
+///
+/// ```no_run
+/// # use ftx::ws::{Data, Orderbook, Player, RecordingFormat};
+/// # use futures::StreamExt;
+/// # async fn example() -> ftx::ws::Result<()> {
+/// let mut player = Player::open("btc-perp.jsonl", RecordingFormat::JsonLines)?.with_speed(10.0);
+/// let mut orderbook = Orderbook::new("BTC-PERP".to_owned());
+/// while let Some((_, data)) = player.next().await.transpose()? {
+///     if let Data::OrderbookData(data) = data {
+///         orderbook.update(&data)?;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Player {
+    reader: Box<dyn BufRead + Send>,
+    format: RecordingFormat,
+    /// `None` to replay without delays
+    speed: Option<f64>,
+    /// The time of the first record and when it was replayed
+    start: Option<(DateTime<Utc>, Instant)>,
+    /// The record waiting for its delay to pass
+    pending: Option<Record>,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl Player {
+    #[allow(unknown_lints, clippy::result_large_err)]
+    pub fn open(path: impl AsRef<Path>, format: RecordingFormat) -> Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?), format))
+    }
+
+    pub fn new(reader: impl BufRead + Send + 'static, format: RecordingFormat) -> Self {
+        Self {
+            reader: Box::new(reader),
+            format,
+            speed: Some(1.0),
+            start: None,
+            pending: None,
+            delay: None,
+        }
+    }
+
+    /// Replays `speed` times faster than the data was received.
+    ///
+    /// Panics if `speed` is not positive, use `without_delays` to replay as
+    /// fast as possible instead.
+    #[must_use]
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        assert!(speed > 0.0, "replay speed must be positive, got {}", speed);
+        self.speed = Some(speed);
+        self
+    }
+
+    /// Replays all data right away
+    #[must_use]
+    pub fn without_delays(mut self) -> Self {
+        self.speed = None;
+        self
+    }
+
+    /// Reads the next record, `None` at the end of the recording.
+    #[allow(unknown_lints, clippy::result_large_err)]
+    pub fn read_record(&mut self) -> Result<Option<Record>> {
+        match self.format {
+            RecordingFormat::JsonLines => loop {
+                let mut line = String::new();
+                if self.reader.read_line(&mut line)? == 0 {
+                    return Ok(None);
+                }
+                if !line.trim().is_empty() {
+                    return Ok(Some(serde_json::from_str(&line)?));
+                }
+            },
+            #[cfg(feature = "cbor")]
+            RecordingFormat::Cbor => {
+                if self.reader.fill_buf()?.is_empty() {
+                    return Ok(None);
+                }
+                ciborium::de::from_reader(&mut self.reader)
+                    .map(Some)
+                    .map_err(|e| Error::Cbor(e.to_string()))
+            }
+        }
+    }
+
+    /// When the record is due, `None` if right away
+    fn due(&mut self, record: &Record) -> Option<Instant> {
+        let speed = self.speed?;
+        let (start_time, started) = *self
+            .start
+            .get_or_insert_with(|| (record.time, Instant::now()));
+        let elapsed = (record.time - start_time).to_std().ok()?;
+        Some(started + Duration::from_secs_f64(elapsed.as_secs_f64() / speed))
+    }
+}
+
+impl Stream for Player {
+    type Item = Result<(Option<Symbol>, Data)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(delay) = self.delay.as_mut() {
+            ready!(delay.as_mut().poll(cx));
+            self.delay = None;
+            if let Some(record) = self.pending.take() {
+                return Poll::Ready(Some(Ok((record.symbol, record.data))));
+            }
+        }
+
+        let record = match self.read_record() {
+            Ok(Some(record)) => record,
+            Ok(None) => return Poll::Ready(None),
+            Err(e) => return Poll::Ready(Some(Err(e))),
+        };
+        match self.due(&record) {
+            Some(due) if due > Instant::now() => {
+                self.pending = Some(record);
+                self.delay = Some(Box::pin(tokio::time::sleep_until(due)));
+                self.poll_next(cx)
+            }
+            _ => Poll::Ready(Some(Ok((record.symbol, record.data)))),
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use super::*;
-use crate::rest::{OrderStatus, Rest};
+use crate::mock::{fill, market, ticker, trade, MockExchange, OrderBehavior};
+use crate::rest::{GetOrderBook, OrderStatus, OrderType, PlaceOrder, Resolution, Rest, Side};
+use chrono::{TimeZone, Utc};
 use dotenvy::dotenv;
+use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
 async fn init_authenticated_ws() -> Ws {
@@ -192,6 +195,68 @@ async fn order_book_helpers() {
         (dec!(20) + dec!(30) + dec!(30)) / dec!(30)
     );
     assert_eq!(ob.quote(Side::Sell, dec!(100)), None);
+
+    // Only positive quantities can be quoted
+    assert_eq!(ob.quote(Side::Buy, dec!(0)), None);
+    assert_eq!(ob.quote(Side::Sell, dec!(-1)), None);
+}
+
+#[test]
+fn order_book_analytics() {
+    let mut ob = Orderbook::new("SHIT-PERP".to_owned());
+    assert_eq!(ob.depth(dec!(100)), None);
+    assert_eq!(ob.price_impact(Side::Buy, &[dec!(1)]), vec![None]);
+
+    ob.asks.insert(dec!(7), dec!(40));
+    ob.asks.insert(dec!(6), dec!(30));
+    ob.asks.insert(dec!(5), dec!(20));
+    ob.bids.insert(dec!(4), dec!(5));
+    ob.bids.insert(dec!(3), dec!(10));
+    ob.bids.insert(dec!(2), dec!(15));
+
+    // Within $1.125 and $2.25 of the mid price of $4.5
+    assert_eq!(ob.depth(dec!(2500)), Some((dec!(5), dec!(20))));
+    assert_eq!(ob.depth(dec!(5000)), Some((dec!(15), dec!(50))));
+    assert_eq!(ob.imbalance(dec!(2500)), Some(dec!(-0.6)));
+
+    assert_eq!(
+        ob.levels_until(Side::Buy, dec!(20)).collect::<Vec<_>>(),
+        vec![(&dec!(5), &dec!(20))]
+    );
+    assert_eq!(
+        ob.levels_until(Side::Buy, dec!(25)).collect::<Vec<_>>(),
+        vec![(&dec!(5), &dec!(20)), (&dec!(6), &dec!(30))]
+    );
+    assert_eq!(ob.levels_until(Side::Sell, dec!(100)).count(), 3);
+
+    // $100 at $5, $30 at $6, the same as 25 contracts
+    assert_eq!(
+        ob.quote_notional(Side::Buy, dec!(130)),
+        ob.quote(Side::Buy, dec!(25))
+    );
+    assert_eq!(ob.quote_notional(Side::Sell, dec!(20)), Some(dec!(4)));
+    assert_eq!(ob.quote_notional(Side::Buy, dec!(1000)), None);
+    assert_eq!(ob.quote_notional(Side::Buy, dec!(0)), None);
+    assert_eq!(ob.quote_notional(Side::Sell, dec!(-20)), None);
+
+    let impacts = ob.price_impact(Side::Buy, &[dec!(50), dec!(25), dec!(100)]);
+    assert_eq!(
+        impacts[0].map(|impact| impact.price),
+        ob.quote(Side::Buy, dec!(50))
+    );
+    assert_eq!(
+        impacts[1],
+        Some(PriceImpact {
+            quantity: dec!(25),
+            price: dec!(5.2),
+            bps: (dec!(5.2) - dec!(4.5)) / dec!(4.5) * dec!(10000),
+        })
+    );
+    assert_eq!(impacts[2], None);
+    assert_eq!(
+        ob.price_impact(Side::Sell, &[dec!(5)])[0].unwrap().bps,
+        (dec!(4.5) - dec!(4)) / dec!(4.5) * dec!(10000)
+    );
 }
 
 #[tokio::test]
@@ -345,3 +410,715 @@ async fn orders() {
 
     ws.unsubscribe_all().await.expect("Unsubscribe failed");
 }
+
+async fn init_mock_ws() -> (MockExchange, Ws) {
+    let mock = MockExchange::start().await.unwrap();
+    let ws = Ws::connect(
+        mock.options()
+            .authenticate("key".to_owned(), "secret".to_owned()),
+    )
+    .await
+    .expect("Connection failed.");
+    (mock, ws)
+}
+
+#[tokio::test]
+async fn mock_order_book_checksum() {
+    let (mock, mut ws) = init_mock_ws().await;
+    mock.set_orderbook(
+        "BTC-PERP",
+        &[(dec!(19999), dec!(0.5)), (dec!(20000), dec!(1.25))],
+        &[(dec!(20001), dec!(0.75)), (dec!(20002), dec!(2))],
+    );
+
+    ws.subscribe(&[Channel::Orderbook("BTC-PERP".to_owned())])
+        .await
+        .expect("Subscription failed.");
+
+    let mut orderbook = Orderbook::new("BTC-PERP".to_owned());
+    match ws.next().await.unwrap() {
+        Ok((_, Data::OrderbookData(data))) if data.action == OrderbookAction::Partial => {
+            orderbook.update(&data).unwrap();
+        }
+        _ => panic!("Order book snapshot data expected."),
+    }
+    assert_eq!(orderbook.best_bid(), Some((&dec!(20000), &dec!(1.25))));
+
+    mock.update_orderbook(
+        "BTC-PERP",
+        &[(dec!(20000), dec!(0))],
+        &[(dec!(20000.5), dec!(0.1))],
+    );
+    match ws.next().await.unwrap() {
+        Ok((_, Data::OrderbookData(data))) if data.action == OrderbookAction::Update => {
+            orderbook.update(&data).unwrap();
+        }
+        _ => panic!("Order book update data expected."),
+    }
+    assert_eq!(orderbook.best_bid(), Some((&dec!(19999), &dec!(0.5))));
+    assert_eq!(orderbook.best_ask(), Some((&dec!(20000.5), &dec!(0.1))));
+}
+
+#[tokio::test]
+async fn mock_ticker_and_fills() {
+    let (mock, mut ws) = init_mock_ws().await;
+
+    ws.subscribe(&[Channel::Ticker("ETH-PERP".to_owned()), Channel::Fills])
+        .await
+        .expect("Subscription failed.");
+
+    mock.publish_ticker("ETH-PERP", ticker(dec!(999), dec!(1001), dec!(1000)));
+    match ws.next().await.unwrap() {
+        Ok((Some(symbol), Data::Ticker(ticker))) => {
+            assert_eq!(symbol, "ETH-PERP");
+            assert_eq!(ticker.last, dec!(1000));
+        }
+        _ => panic!("Ticker data expected."),
+    }
+
+    mock.publish_fill(fill(
+        1,
+        "ETH-PERP",
+        Side::Buy,
+        dec!(1000),
+        dec!(0.1),
+        chrono::Utc::now(),
+    ));
+    match ws.next().await.unwrap() {
+        Ok((_, Data::Fill(fill))) => assert_eq!(fill.size, dec!(0.1)),
+        _ => panic!("Fill data expected."),
+    }
+}
+
+#[tokio::test]
+async fn mock_subscribe_authenticated_updates_on_unauthenticated_channel() {
+    let mock = MockExchange::start().await.unwrap();
+    let mut ws = Ws::connect(mock.options())
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(&[Channel::Orders])
+        .await
+        .expect_err("Subscription should fail");
+}
+
+#[tokio::test]
+async fn mock_orders() {
+    let (mock, mut ws) = init_mock_ws().await;
+    let api = Rest::new(
+        mock.options()
+            .authenticate("key".to_owned(), "secret".to_owned()),
+    );
+
+    ws.subscribe(&[Channel::Orders])
+        .await
+        .expect("Subscription failed.");
+
+    mock.push_order_behavior(OrderBehavior::Reject);
+    let order = api
+        .request(PlaceOrder {
+            market: "BTC-PERP",
+            side: Side::Buy,
+            price: Some(dec!(20000)),
+            r#type: OrderType::Limit,
+            size: dec!(0.001),
+            post_only: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // Rejected post-only orders are closed without being filled
+    match ws.next().await.unwrap() {
+        Ok((_, Data::Order(closed))) => {
+            assert_eq!(order.id, closed.id);
+            assert_eq!(OrderStatus::Closed, closed.status);
+            assert_eq!(dec!(0), closed.filled_size.unwrap());
+        }
+        _ => panic!("Order data expected."),
+    }
+}
+
+#[tokio::test]
+async fn mock_reconnect() {
+    let (mock, ws) = init_mock_ws().await;
+    let mut ws = ws.with_reconnect(ReconnectPolicy {
+        max_attempts: Some(3),
+        delay: std::time::Duration::from_millis(10),
+    });
+    mock.set_orderbook(
+        "BTC-PERP",
+        &[(dec!(19999), dec!(1))],
+        &[(dec!(20001), dec!(1))],
+    );
+
+    ws.subscribe(&[Channel::Orderbook("BTC-PERP".to_owned()), Channel::Orders])
+        .await
+        .expect("Subscription failed.");
+    let mut orderbook = Orderbook::new("BTC-PERP".to_owned());
+    match ws.next().await.unwrap() {
+        Ok((_, Data::OrderbookData(data))) => orderbook.update(&data).unwrap(),
+        _ => panic!("Order book snapshot data expected."),
+    }
+
+    mock.disconnect_all();
+    mock.set_orderbook(
+        "BTC-PERP",
+        &[(dec!(19998), dec!(2))],
+        &[(dec!(20002), dec!(2))],
+    );
+
+    match ws.next().await.unwrap() {
+        Ok((None, Data::Disconnected)) => {}
+        _ => panic!("Disconnection expected."),
+    }
+    match ws.next().await.unwrap() {
+        Ok((None, Data::Reconnected)) => {}
+        _ => panic!("Reconnection expected."),
+    }
+    assert_eq!(1, mock.ws_connections());
+    assert_eq!(2, ws.channels().len());
+
+    // The orderbook starts over with a partial
+    match ws.next().await.unwrap() {
+        Ok((_, Data::OrderbookData(data))) if data.action == OrderbookAction::Partial => {
+            orderbook.update(&data).unwrap();
+        }
+        _ => panic!("Order book snapshot data expected."),
+    }
+    assert_eq!(orderbook.best_bid(), Some((&dec!(19998), &dec!(2))));
+    assert_eq!(orderbook.bids.len(), 1);
+
+    // Authenticated channels are subscribed to again as well
+    let api = Rest::new(
+        mock.options()
+            .authenticate("key".to_owned(), "secret".to_owned()),
+    );
+    let order = api
+        .request(PlaceOrder {
+            market: "BTC-PERP",
+            side: Side::Buy,
+            price: Some(dec!(19000)),
+            r#type: OrderType::Limit,
+            size: dec!(0.001),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    match ws.next().await.unwrap() {
+        Ok((_, Data::Order(update))) => assert_eq!(order.id, update.id),
+        _ => panic!("Order data expected."),
+    }
+}
+
+#[tokio::test]
+async fn mock_reconnect_gives_up() {
+    let (mock, ws) = init_mock_ws().await;
+    let mut ws = ws.with_reconnect(ReconnectPolicy {
+        max_attempts: Some(1),
+        delay: std::time::Duration::from_millis(10),
+    });
+
+    // Stops the servers as well
+    drop(mock);
+    match ws.next().await.unwrap() {
+        Ok((None, Data::Disconnected)) => {}
+        _ => panic!("Disconnection expected."),
+    }
+    assert!(ws.next().await.unwrap().is_err());
+    // The stream ends instead of reconnecting again
+    assert!(ws.next().await.is_none());
+    assert!(ws.next().await.is_none());
+}
+
+#[tokio::test]
+async fn mock_connection_closed() {
+    let (mock, mut ws) = init_mock_ws().await;
+
+    mock.disconnect_all();
+    match ws.next().await.unwrap() {
+        Err(Error::ConnectionClosed) => {}
+        other => panic!("Connection closed error expected, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn mock_orderbook_stream() {
+    let (mock, ws) = init_mock_ws().await;
+    mock.set_orderbook(
+        "ETH-PERP",
+        &[(dec!(999), dec!(1)), (dec!(998), dec!(2))],
+        &[(dec!(1001), dec!(1))],
+    );
+
+    let mut orderbooks = OrderbookStream::new(ws);
+    orderbooks
+        .subscribe(&["ETH-PERP".to_owned()])
+        .await
+        .expect("Subscription failed.");
+
+    match orderbooks.next().await.unwrap().unwrap() {
+        OrderbookEvent::Snapshot(orderbook) => {
+            assert_eq!(orderbook.symbol, "ETH-PERP");
+            assert_eq!(orderbook.bids.len(), 2);
+        }
+        event => panic!("Snapshot expected, got {:?}", event),
+    }
+
+    mock.update_orderbook("ETH-PERP", &[(dec!(999), dec!(0))], &[]);
+    match orderbooks.next().await.unwrap().unwrap() {
+        OrderbookEvent::Update(symbol, data) => {
+            assert_eq!(symbol, "ETH-PERP");
+            assert_eq!(data.bids, vec![(dec!(999), dec!(0))]);
+        }
+        event => panic!("Update expected, got {:?}", event),
+    }
+    assert_eq!(
+        orderbooks.orderbook("ETH-PERP").unwrap().best_bid(),
+        Some((&dec!(998), &dec!(2)))
+    );
+
+    // An update with an incorrect checksum triggers a resync
+    mock.publish(
+        &Channel::Orderbook("ETH-PERP".to_owned()),
+        Type::Update,
+        &ResponseData::OrderbookData(OrderbookData {
+            action: OrderbookAction::Update,
+            bids: vec![(dec!(997), dec!(5))],
+            asks: vec![],
+            checksum: 0,
+            time: chrono::Utc::now(),
+        }),
+    );
+    match orderbooks.next().await.unwrap().unwrap() {
+        OrderbookEvent::Resyncing(symbol) => assert_eq!(symbol, "ETH-PERP"),
+        event => panic!("Resyncing expected, got {:?}", event),
+    }
+    assert!(!orderbooks.orderbook("ETH-PERP").unwrap().is_initialized());
+
+    match orderbooks.next().await.unwrap().unwrap() {
+        OrderbookEvent::Snapshot(orderbook) => {
+            assert_eq!(orderbook.bids.len(), 1);
+            assert_eq!(orderbook.best_bid(), Some((&dec!(998), &dec!(2))));
+        }
+        event => panic!("Snapshot expected, got {:?}", event),
+    }
+    assert_eq!(1, mock.ws_connections());
+}
+
+async fn next_market_changes(ws: &mut Ws, registry: &mut MarketRegistry) -> Vec<MarketChange> {
+    match ws.next().await.unwrap() {
+        Ok((None, Data::Markets(data))) => {
+            let mut changes = registry.update(&data);
+            changes.sort_by_key(|change| format!("{:?}", change));
+            changes
+        }
+        other => panic!("Markets data expected, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn mock_markets() {
+    let (mock, mut ws) = init_mock_ws().await;
+    mock.add_market(market("BTC-PERP", dec!(1), dec!(0.0001)));
+    mock.add_market(market("ETH-PERP", dec!(0.1), dec!(0.001)));
+
+    ws.subscribe(&[Channel::Markets])
+        .await
+        .expect("Subscription failed.");
+
+    let mut registry = MarketRegistry::new();
+    let changes = next_market_changes(&mut ws, &mut registry).await;
+    assert!(registry.is_initialized());
+    assert_eq!(registry.len(), 2);
+    assert_eq!(
+        changes,
+        vec![
+            MarketChange::Listed("BTC-PERP".to_owned()),
+            MarketChange::Listed("ETH-PERP".to_owned()),
+        ]
+    );
+
+    mock.add_market(market("SOL-PERP", dec!(0.01), dec!(0.1)));
+    let changes = next_market_changes(&mut ws, &mut registry).await;
+    assert_eq!(changes, vec![MarketChange::Listed("SOL-PERP".to_owned())]);
+
+    mock.add_market(market("BTC-PERP", dec!(0.5), dec!(0.0001)));
+    let changes = next_market_changes(&mut ws, &mut registry).await;
+    assert_eq!(
+        changes,
+        vec![MarketChange::IncrementsChanged("BTC-PERP".to_owned())]
+    );
+    assert_eq!(registry.get("BTC-PERP").unwrap().price_increment, dec!(0.5));
+
+    mock.remove_market("ETH-PERP");
+    let changes = next_market_changes(&mut ws, &mut registry).await;
+    assert_eq!(changes, vec![MarketChange::Delisted("ETH-PERP".to_owned())]);
+    assert!(registry.get("ETH-PERP").is_none());
+    assert_eq!(registry.len(), 2);
+}
+
+#[tokio::test]
+async fn mock_grouped_orderbook() {
+    let (mock, mut ws) = init_mock_ws().await;
+    mock.set_orderbook(
+        "BTC-PERP",
+        &[
+            (dec!(99), dec!(1)),
+            (dec!(98), dec!(2)),
+            (dec!(94), dec!(3)),
+        ],
+        &[
+            (dec!(101), dec!(1)),
+            (dec!(103), dec!(2)),
+            (dec!(107), dec!(3)),
+        ],
+    );
+
+    let channel = Channel::GroupedOrderbook {
+        market: "BTC-PERP".to_owned(),
+        grouping: dec!(5),
+    };
+    ws.subscribe(&[channel])
+        .await
+        .expect("Subscription failed.");
+
+    let mut orderbook = GroupedOrderbook::new("BTC-PERP".to_owned(), dec!(5));
+    match ws.next().await.unwrap() {
+        Ok((Some(symbol), Data::GroupedOrderbookData(data))) => {
+            assert_eq!(symbol, "BTC-PERP");
+            assert_eq!(data.action, OrderbookAction::Partial);
+            orderbook.update(&data).unwrap();
+        }
+        other => panic!("Grouped orderbook data expected, got {:?}", other),
+    }
+    assert!(orderbook.is_initialized());
+    assert_eq!(
+        orderbook.bids.clone().into_iter().collect::<Vec<_>>(),
+        vec![(dec!(90), dec!(3)), (dec!(95), dec!(3))]
+    );
+    assert_eq!(
+        orderbook.asks.clone().into_iter().collect::<Vec<_>>(),
+        vec![(dec!(105), dec!(3)), (dec!(110), dec!(3))]
+    );
+
+    mock.update_orderbook("BTC-PERP", &[(dec!(99), dec!(0))], &[(dec!(107), dec!(0))]);
+    match ws.next().await.unwrap() {
+        Ok((_, Data::GroupedOrderbookData(data))) => {
+            assert_eq!(data.action, OrderbookAction::Update);
+            orderbook.update(&data).unwrap();
+        }
+        other => panic!("Grouped orderbook data expected, got {:?}", other),
+    }
+    assert_eq!(orderbook.best_bid(), Some((&dec!(95), &dec!(2))));
+    assert_eq!(orderbook.best_ask(), Some((&dec!(105), &dec!(3))));
+    assert!(!orderbook.asks.contains_key(&dec!(110)));
+}
+
+#[test]
+fn candle_builder() {
+    // A multiple of a minute, like the start times of FTX candles
+    let t0 = Utc.timestamp_opt(1_600_000_020, 0).unwrap();
+    let seconds = |s: i64| t0 + chrono::Duration::seconds(s);
+    let mut builder = CandleBuilder::new("BTC-PERP".to_owned(), Resolution::Minute)
+        .with_vwap()
+        .with_side_volume()
+        .with_liquidation_volume();
+
+    assert!(builder
+        .apply_trade(&trade(1, Side::Buy, dec!(10), dec!(1), seconds(10)))
+        .is_empty());
+    let mut liquidation = trade(2, Side::Sell, dec!(12), dec!(3), seconds(20));
+    liquidation.liquidation = true;
+    builder.apply_trade(&liquidation);
+    // Arrived late, but still opens the candle
+    builder.apply_trade(&trade(3, Side::Buy, dec!(11), dec!(1), seconds(5)));
+    // Applied twice, e.g. over websockets and REST
+    builder.apply_trade(&trade(3, Side::Buy, dec!(11), dec!(1), seconds(5)));
+
+    let current = builder.current().unwrap();
+    assert_eq!(3, current.trades);
+    assert_eq!(
+        (dec!(11), dec!(12), dec!(10), dec!(12), dec!(57)),
+        (
+            current.candle.open,
+            current.candle.high,
+            current.candle.low,
+            current.candle.close,
+            current.candle.volume
+        )
+    );
+    assert_eq!(Some(dec!(11.4)), current.vwap);
+    assert_eq!(Some(dec!(21)), current.buy_volume);
+    assert_eq!(Some(dec!(36)), current.sell_volume);
+    assert_eq!(Some(dec!(36)), current.liquidation_volume);
+
+    // The third minute completes the first and the empty second minute
+    let completed = builder.update(
+        Some("BTC-PERP"),
+        &Data::Trade(trade(4, Side::Sell, dec!(9), dec!(1), seconds(130))),
+    );
+    assert_eq!(2, completed.len());
+    assert_eq!(t0, completed[0].candle.start_time);
+    assert_eq!(seconds(60), completed[1].candle.start_time);
+    assert_eq!(dec!(12), completed[1].candle.open);
+    assert_eq!(Decimal::ZERO, completed[1].candle.volume);
+    assert_eq!(None, completed[1].vwap);
+
+    // Trades of completed candles and other markets are ignored
+    assert!(builder
+        .apply_trade(&trade(5, Side::Sell, dec!(1), dec!(1), seconds(70)))
+        .is_empty());
+    builder.update(
+        Some("ETH-PERP"),
+        &Data::Trade(trade(6, Side::Sell, dec!(1), dec!(1), seconds(140))),
+    );
+    assert_eq!(1, builder.current().unwrap().trades);
+
+    assert!(builder.flush(seconds(179)).is_empty());
+    let completed = builder.flush(seconds(180));
+    assert_eq!(1, completed.len());
+    assert_eq!(dec!(9), completed[0].candle.close);
+}
+
+#[tokio::test]
+async fn mock_candle_builder_backfill() {
+    let (mock, mut ws) = init_mock_ws().await;
+    let rest = Rest::new(mock.options());
+    let t0 = Utc.timestamp_opt(1_600_000_020, 0).unwrap();
+    let seconds = |s: i64| t0 + chrono::Duration::seconds(s);
+    ws.subscribe(&[Channel::Trades("BTC-PERP".to_owned())])
+        .await
+        .unwrap();
+
+    // Trades before the websocket connected, and one of the previous minute
+    mock.add_trades(
+        "BTC-PERP",
+        vec![
+            trade(1, Side::Buy, dec!(99), dec!(1), seconds(-5)),
+            trade(2, Side::Buy, dec!(100), dec!(1), seconds(5)),
+            trade(3, Side::Sell, dec!(98), dec!(2), seconds(10)),
+        ],
+    );
+    mock.publish_trades(
+        "BTC-PERP",
+        vec![trade(4, Side::Buy, dec!(101), dec!(1), seconds(20))],
+    );
+
+    let mut builder = CandleBuilder::new("BTC-PERP".to_owned(), Resolution::Minute);
+    let (symbol, data) = ws.next().await.unwrap().unwrap();
+    assert!(builder.update(symbol.as_deref(), &data).is_empty());
+    assert!(builder.backfill(&rest).await.unwrap().is_empty());
+
+    let current = builder.current().unwrap();
+    assert_eq!(3, current.trades);
+    assert_eq!(
+        (dec!(100), dec!(101), dec!(98), dec!(101)),
+        (
+            current.candle.open,
+            current.candle.high,
+            current.candle.low,
+            current.candle.close
+        )
+    );
+    assert_eq!(None, current.vwap);
+}
+
+/// An orderbook partial with a correct checksum, a trade, a ticker and a
+/// fill, which is not recorded
+fn recordable_data() -> Vec<(Option<Symbol>, Data)> {
+    // Timestamps are exchanged as floating point seconds, which preserves
+    // microseconds
+    let time = Utc.timestamp_opt(1_600_000_000, 123_456_000).unwrap();
+    let mut orderbook = Orderbook::new("BTC-PERP".to_owned());
+    orderbook.bids.insert(dec!(99.5), dec!(2));
+    orderbook.asks.insert(dec!(100), dec!(1.5));
+    let partial = OrderbookData {
+        action: OrderbookAction::Partial,
+        bids: vec![(dec!(99.5), dec!(2))],
+        asks: vec![(dec!(100), dec!(1.5))],
+        checksum: orderbook.checksum(),
+        time,
+    };
+    let symbol = Some("BTC-PERP".to_owned());
+
+    vec![
+        (symbol.clone(), Data::OrderbookData(partial)),
+        (
+            symbol.clone(),
+            Data::Trade(trade(1, Side::Buy, dec!(100), dec!(0.5), time)),
+        ),
+        (
+            None,
+            Data::Fill(fill(1, "BTC-PERP", Side::Buy, dec!(100), dec!(0.5), time)),
+        ),
+        (
+            symbol,
+            Data::Ticker(Ticker {
+                time,
+                ..ticker(dec!(99.5), dec!(100), dec!(100))
+            }),
+        ),
+        (None, Data::Reconnected),
+    ]
+}
+
+async fn replay(player: Player) -> Vec<(Option<Symbol>, Data)> {
+    futures::TryStreamExt::try_collect(player).await.unwrap()
+}
+
+fn assert_replayed(recorded: &[(Option<Symbol>, Data)], replayed: &[(Option<Symbol>, Data)]) {
+    let recorded: Vec<_> = recorded
+        .iter()
+        .filter(|(_, data)| !matches!(data, Data::Fill(_)))
+        .collect();
+    assert_eq!(recorded.len(), replayed.len());
+    for ((symbol, data), (replayed_symbol, replayed_data)) in recorded.into_iter().zip(replayed) {
+        assert_eq!(symbol, replayed_symbol);
+        assert_eq!(
+            serde_json::to_value(data).unwrap(),
+            serde_json::to_value(replayed_data).unwrap()
+        );
+    }
+}
+
+#[tokio::test]
+async fn record_and_replay() {
+    let data = recordable_data();
+    let t0 = Utc::now();
+
+    let mut recorder = Recorder::new(Vec::new(), RecordingFormat::JsonLines);
+    for (i, (symbol, data)) in data.iter().enumerate() {
+        let time = t0 + chrono::Duration::milliseconds(100 * i as i64);
+        recorder.record_at(time, symbol.as_deref(), data).unwrap();
+    }
+    let recording = recorder.into_inner();
+    assert_eq!(
+        4,
+        recording
+            .split(|b| *b == b'\n')
+            .filter(|l| !l.is_empty())
+            .count()
+    );
+
+    let replayed = replay(
+        Player::new(
+            std::io::Cursor::new(recording.clone()),
+            RecordingFormat::JsonLines,
+        )
+        .without_delays(),
+    )
+    .await;
+    assert_replayed(&data, &replayed);
+
+    // The replayed orderbook data is consistent with its checksum
+    let mut orderbook = Orderbook::new("BTC-PERP".to_owned());
+    match &replayed[0].1 {
+        Data::OrderbookData(data) => orderbook.update(data).unwrap(),
+        data => panic!("Orderbook data expected, got {:?}", data),
+    }
+
+    // 400ms of data at four times the speed
+    let started = std::time::Instant::now();
+    let replayed = replay(
+        Player::new(std::io::Cursor::new(recording), RecordingFormat::JsonLines).with_speed(4.0),
+    )
+    .await;
+    let elapsed = started.elapsed();
+    assert_replayed(&data, &replayed);
+    assert!(elapsed >= Duration::from_millis(100));
+    assert!(elapsed < Duration::from_millis(400));
+}
+
+#[test]
+fn replay_speed_must_be_positive() {
+    for speed in &[0.0, -1.0, f64::NAN] {
+        let result = std::panic::catch_unwind(|| {
+            Player::new(std::io::empty(), RecordingFormat::JsonLines).with_speed(*speed)
+        });
+        assert!(result.is_err(), "speed {} was accepted", speed);
+    }
+}
+
+#[cfg(feature = "cbor")]
+#[tokio::test]
+async fn record_and_replay_cbor() {
+    let data = recordable_data();
+
+    let mut recorder = Recorder::new(Vec::new(), RecordingFormat::Cbor);
+    for (symbol, data) in &data {
+        recorder.record(symbol.as_deref(), data).unwrap();
+    }
+    let recording = recorder.into_inner();
+
+    let replayed = replay(
+        Player::new(std::io::Cursor::new(recording), RecordingFormat::Cbor).without_delays(),
+    )
+    .await;
+    assert_replayed(&data, &replayed);
+}
+
+/// The best levels, mid price, depth and quotes of any orderbook
+fn inspect(orderbook: &impl OrderbookView) -> Vec<Option<Decimal>> {
+    let depth = orderbook.depth(dec!(100));
+    vec![
+        orderbook.top_bid().map(|(price, _)| price),
+        orderbook.top_ask().map(|(price, _)| price),
+        orderbook.mid_price(),
+        depth.map(|(bids, _)| bids),
+        depth.map(|(_, asks)| asks),
+        orderbook.quote(Side::Buy, dec!(3)),
+        orderbook.quote(Side::Sell, dec!(10)),
+    ]
+}
+
+#[tokio::test]
+async fn mock_orderbook_snapshot() {
+    let (mock, mut ws) = init_mock_ws().await;
+    let rest = Rest::new(mock.options());
+    mock.set_orderbook(
+        "BTC-PERP",
+        &[
+            (dec!(99.5), dec!(1)),
+            (dec!(99), dec!(2)),
+            (dec!(90), dec!(5)),
+        ],
+        &[(dec!(100), dec!(2)), (dec!(100.5), dec!(1.5))],
+    );
+
+    let snapshot = rest.request(GetOrderBook::new("BTC-PERP")).await.unwrap();
+    let from_snapshot = Orderbook::from_snapshot("BTC-PERP".to_owned(), &snapshot);
+    assert!(from_snapshot.is_initialized());
+
+    ws.subscribe(&[Channel::Orderbook("BTC-PERP".to_owned())])
+        .await
+        .unwrap();
+    let mut orderbook = Orderbook::new("BTC-PERP".to_owned());
+    match ws.next().await.unwrap().unwrap() {
+        (_, Data::OrderbookData(data)) => orderbook.update(&data).unwrap(),
+        data => panic!("Orderbook data expected, got {:?}", data),
+    }
+    assert_eq!(orderbook.checksum(), from_snapshot.checksum());
+
+    // The snapshot initializes an orderbook like a partial
+    let mut from_partial = Orderbook::new("BTC-PERP".to_owned());
+    from_partial
+        .update(&OrderbookData::from_snapshot(&snapshot))
+        .unwrap();
+    assert_eq!(orderbook.checksum(), from_partial.checksum());
+
+    let inspected = inspect(&snapshot);
+    assert_eq!(inspected, inspect(&orderbook));
+    assert_eq!(
+        inspected,
+        vec![
+            Some(dec!(99.5)),
+            Some(dec!(100)),
+            Some(dec!(99.75)),
+            // Within $0.9975 of the mid price
+            Some(dec!(3)),
+            Some(dec!(3.5)),
+            Some((dec!(200) + dec!(100.5)) / dec!(3)),
+            None,
+        ]
+    );
+}